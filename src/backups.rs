@@ -0,0 +1,282 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::resolve_config_dir;
+use anyhow::{anyhow, Error};
+
+/// How many backups we keep before deleting the oldest
+pub const MAX_BACKUPS: usize = 20;
+
+const BACKUP_PREFIX: &str = "ModsConfig-";
+
+/// A copy of ModsConfig.xml taken before we overwrote it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub created: SystemTime,
+}
+
+pub fn resolve_backup_dir() -> Option<PathBuf> {
+    Some(resolve_config_dir()?.join("backups"))
+}
+
+/// Each ModsConfig.xml gets its own folder so a backup can't be restored over a different file
+/// The folder is named after the full path with everything but letters and numbers replaced
+fn backup_dir_for(backup_root: &Path, modconfig_xml_path: &Path) -> PathBuf {
+    let full_path = modconfig_xml_path
+        .canonicalize()
+        .or_else(|_| std::path::absolute(modconfig_xml_path))
+        .unwrap_or_else(|_| modconfig_xml_path.to_path_buf());
+    let folder_name: String = full_path
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+
+    backup_root.join(folder_name)
+}
+
+/// Writes to a temporary file next to the target and renames it over the target
+/// The rename is atomic so the game never sees a half written file
+pub fn write_file_atomically(path: &Path, data: &[u8]) -> Result<(), Error> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| anyhow!("{} is not a file", path.display()))?;
+    let mut temporary_file_name = file_name.to_os_string();
+    temporary_file_name.push(".tmp");
+    let temporary_path = path.with_file_name(temporary_file_name);
+
+    let mut file = File::create(&temporary_path)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    drop(file);
+
+    if let Err(error) = fs::rename(&temporary_path, path) {
+        let _ = fs::remove_file(&temporary_path);
+        return Err(error.into());
+    }
+
+    Ok(())
+}
+
+/// Copies the file into the backup folder and deletes the oldest backups past [MAX_BACKUPS]
+/// Does nothing if there is no file to back up yet
+pub fn backup_modconfig_xml(modconfig_xml_path: &Path) -> Result<Option<PathBuf>, Error> {
+    let backup_root = resolve_backup_dir().ok_or_else(|| anyhow!("Can't find the config dir"))?;
+    backup_into(&backup_root, modconfig_xml_path)
+}
+
+fn backup_into(backup_root: &Path, modconfig_xml_path: &Path) -> Result<Option<PathBuf>, Error> {
+    if !modconfig_xml_path.is_file() {
+        return Ok(None);
+    }
+
+    let backup_dir = backup_dir_for(backup_root, modconfig_xml_path);
+    fs::create_dir_all(&backup_dir)?;
+
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let mut backup_path = backup_dir.join(format!("{}{}.xml", BACKUP_PREFIX, timestamp));
+
+    // Two saves in the same millisecond, unlikely but cheap to handle
+    let mut collision_count = 0;
+    while backup_path.exists() {
+        collision_count += 1;
+        backup_path = backup_dir.join(format!(
+            "{}{}-{}.xml",
+            BACKUP_PREFIX, timestamp, collision_count
+        ));
+    }
+
+    fs::copy(modconfig_xml_path, &backup_path)?;
+    log::info!("Backed up ModsConfig.xml to {}", backup_path.display());
+
+    for old_backup in list_backups_in(&backup_dir)?.into_iter().skip(MAX_BACKUPS) {
+        log::info!("Removing old backup {}", old_backup.path.display());
+        fs::remove_file(old_backup.path)?;
+    }
+
+    Ok(Some(backup_path))
+}
+
+/// Every backup of this ModsConfig.xml, newest first
+pub fn list_backups(modconfig_xml_path: &Path) -> Result<Vec<Backup>, Error> {
+    let Some(backup_root) = resolve_backup_dir() else {
+        return Ok(Vec::new());
+    };
+
+    list_backups_in(&backup_dir_for(&backup_root, modconfig_xml_path))
+}
+
+fn list_backups_in(backup_dir: &Path) -> Result<Vec<Backup>, Error> {
+    if !backup_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = Vec::new();
+
+    for entry in backup_dir.read_dir()? {
+        let path = entry?.path();
+
+        let Some(timestamp) = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .and_then(|stem| stem.strip_prefix(BACKUP_PREFIX))
+            .and_then(|stem| stem.split('-').next())
+            .and_then(|timestamp| timestamp.parse().ok())
+        else {
+            continue;
+        };
+
+        backups.push(Backup {
+            path,
+            created: UNIX_EPOCH + Duration::from_millis(timestamp),
+        });
+    }
+
+    backups.sort_by(|a, b| b.created.cmp(&a.created).then_with(|| b.path.cmp(&a.path)));
+
+    Ok(backups)
+}
+
+/// Puts a backup back in place, backing up the current file first so the restore can be undone
+/// Refuses backups that were taken of a different file
+pub fn restore_backup(backup: &Backup, modconfig_xml_path: &Path) -> Result<(), Error> {
+    let backup_root = resolve_backup_dir().ok_or_else(|| anyhow!("Can't find the config dir"))?;
+    restore_from(&backup_root, backup, modconfig_xml_path)
+}
+
+fn restore_from(
+    backup_root: &Path,
+    backup: &Backup,
+    modconfig_xml_path: &Path,
+) -> Result<(), Error> {
+    if backup.path.parent() != Some(&backup_dir_for(backup_root, modconfig_xml_path)) {
+        return Err(anyhow!(
+            "{} is not a backup of {}",
+            backup.path.display(),
+            modconfig_xml_path.display()
+        ));
+    }
+
+    let data = fs::read(&backup.path)?;
+
+    backup_into(backup_root, modconfig_xml_path)?;
+    write_file_atomically(modconfig_xml_path, &data)?;
+
+    Ok(())
+}
+
+/// Formats the time as YYYY-MM-DD HH:MM:SS in UTC
+pub fn format_timestamp(time: SystemTime) -> String {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let (days, seconds_of_day) = (seconds / 86400, seconds % 86400);
+
+    // Howard Hinnant's days to civil date algorithm
+    let days = days as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds_of_day / 3600,
+        seconds_of_day % 3600 / 60,
+        seconds_of_day % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporary_dir;
+
+    #[test]
+    fn timestamps_are_formatted_as_utc_dates() {
+        assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01 00:00:00");
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(951_827_696)),
+            "2000-02-29 12:34:56"
+        );
+        assert_eq!(
+            format_timestamp(UNIX_EPOCH + Duration::from_secs(1_735_689_599)),
+            "2024-12-31 23:59:59"
+        );
+    }
+
+    #[test]
+    fn only_the_newest_backups_are_kept() {
+        let dir = temporary_dir("backup-limit");
+        let backup_root = dir.join("backups");
+        let modconfig_xml_path = dir.join("ModsConfig.xml");
+        fs::write(&modconfig_xml_path, "<ModsConfigData />").unwrap();
+
+        let backup_dir = backup_dir_for(&backup_root, &modconfig_xml_path);
+        fs::create_dir_all(&backup_dir).unwrap();
+        for timestamp in 1..=MAX_BACKUPS {
+            fs::write(
+                backup_dir.join(format!("{}{}.xml", BACKUP_PREFIX, timestamp)),
+                "",
+            )
+            .unwrap();
+        }
+
+        let newest = backup_into(&backup_root, &modconfig_xml_path)
+            .unwrap()
+            .unwrap();
+        let backups = list_backups_in(&backup_dir).unwrap();
+
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert_eq!(backups[0].path, newest);
+        assert!(!backup_dir.join(format!("{}1.xml", BACKUP_PREFIX)).exists());
+        assert!(backup_dir.join(format!("{}2.xml", BACKUP_PREFIX)).exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn backups_only_restore_to_the_file_they_came_from() {
+        let dir = temporary_dir("backup-restore");
+        let backup_root = dir.join("backups");
+        let first_path = dir.join("first.xml");
+        let second_path = dir.join("second.xml");
+        fs::write(&first_path, "first").unwrap();
+        fs::write(&second_path, "second").unwrap();
+
+        backup_into(&backup_root, &first_path).unwrap();
+        let backup = list_backups_in(&backup_dir_for(&backup_root, &first_path))
+            .unwrap()
+            .remove(0);
+        assert!(list_backups_in(&backup_dir_for(&backup_root, &second_path))
+            .unwrap()
+            .is_empty());
+
+        assert!(restore_from(&backup_root, &backup, &second_path).is_err());
+        assert_eq!(fs::read_to_string(&second_path).unwrap(), "second");
+
+        fs::write(&first_path, "changed").unwrap();
+        restore_from(&backup_root, &backup, &first_path).unwrap();
+        assert_eq!(fs::read_to_string(&first_path).unwrap(), "first");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,617 @@
+use std::path::PathBuf;
+
+use crate::{
+    backups::{format_timestamp, list_backups, restore_backup},
+    discovery::{discover_installations, find_steam_prefix_for_game},
+    does_directory_represent_valid_game_installation, does_directory_represent_valid_steam_prefix,
+    errors::FileError,
+    managment::{format_cycle, PackageId},
+    profiles::ProfileStore,
+    settings::Settings,
+    ui::RimManager,
+    version::GameVersion,
+    xml::{read_modconfig_xml_or_default_from, resolve_modconfig_xml_path, write_modconfig_xml_to},
+};
+use anyhow::{anyhow, Error};
+
+// Headless mode so mod lists can be checked from scripts and pre-commit hooks without a window
+// Exit codes: 0 means the list is fine, 1 means the list has unresolved issues, 2 means we couldn't even look at it
+
+const USAGE: &str = "Usage: rimmanager [COMMAND] [OPTIONS]
+
+Launches the user interface when no command is given
+
+Commands:
+  scan     List every mod found in the installation
+  check    Print the issues in the mod list
+  sort     Fix the mod list and print the resulting order without saving it
+  save     Fix the mod list and write it back to the ModsConfig.xml
+  detect   List the RimWorld installations found in the usual places
+  backups  List the backups of the ModsConfig.xml, newest first
+  restore <NUMBER>
+           Restore a backup using its number from the backups command
+  help     Print this message
+
+Options:
+  --game <PATH>     Path to the RimWorld installation, defaults to the one saved by the user interface
+  --steam <PATH>    Path to the Steam prefix, defaults to the one saved by the user interface
+  --mods <PATH>     Extra folder containing mods, added to the ones saved by the user interface, can be repeated
+  --config <PATH>   ModsConfig.xml to use instead of the one the game uses
+  --profile <NAME>  Use the mod list from a profile instead of the ModsConfig.xml
+  --game-version <MAJOR.MINOR>
+                    Check the mods against another version of RimWorld instead of the installed one";
+
+#[derive(Debug, PartialEq, Eq)]
+enum Command {
+    Scan,
+    Check,
+    Sort,
+    Save,
+}
+
+struct CliOptions {
+    command: Command,
+    /// What the user interface remembers with the paths from the arguments on top
+    settings: Settings,
+    modconfig_xml_path: PathBuf,
+    profile: Option<String>,
+    game_version_override: Option<GameVersion>,
+}
+
+/// Paths that aren't given fall back to the settings
+fn parse_arguments(args: &[String], mut settings: Settings) -> Result<Option<CliOptions>, Error> {
+    let mut args = args.iter();
+
+    let command = match args.next().map(String::as_str) {
+        Some("scan") => Command::Scan,
+        Some("check") => Command::Check,
+        Some("sort") => Command::Sort,
+        Some("save") => Command::Save,
+        Some("help" | "--help" | "-h") | None => return Ok(None),
+        Some(other) => return Err(anyhow!("Unknown command: {}", other)),
+    };
+
+    let mut game_path = None;
+    let mut steam_path = None;
+    let mut mod_folder_paths = Vec::new();
+    let mut modconfig_xml_path = None;
+    let mut profile = None;
+    let mut game_version_override = None;
+
+    while let Some(option) = args.next() {
+        let mut value = || {
+            args.next()
+                .map(PathBuf::from)
+                .ok_or_else(|| anyhow!("Missing value for {}", option))
+        };
+
+        match option.as_str() {
+            "--game" => game_path = Some(value()?),
+            "--steam" => steam_path = Some(value()?),
+            "--mods" => mod_folder_paths.push(value()?),
+            "--config" => modconfig_xml_path = Some(value()?),
+            "--profile" => profile = Some(value()?.to_string_lossy().to_string()),
+            "--game-version" => {
+                game_version_override =
+                    Some(GameVersion::parse_major_minor(&value()?.to_string_lossy())?)
+            }
+            "--help" | "-h" => return Ok(None),
+            other => return Err(anyhow!("Unknown option: {}", other)),
+        }
+    }
+
+    let game_path = game_path
+        .or(settings.game_path.take())
+        .ok_or_else(|| anyhow!("--game is required"))?;
+
+    if !does_directory_represent_valid_game_installation(&game_path) {
+        return Err(anyhow!(
+            "{} does not represent a valid RimWorld installation",
+            game_path.display()
+        ));
+    }
+
+    let steam_path = match (steam_path, settings.steam_path.take()) {
+        (Some(steam_path), _) => {
+            if !does_directory_represent_valid_steam_prefix(&steam_path) {
+                return Err(anyhow!(
+                    "{} does not represent a valid Steam prefix",
+                    steam_path.display()
+                ));
+            }
+
+            Some(steam_path)
+        }
+        (None, Some(saved_steam_path))
+            if !does_directory_represent_valid_steam_prefix(&saved_steam_path) =>
+        {
+            // The saved prefix can go stale without the user doing anything, so it isn't worth failing over
+            eprintln!(
+                "warning: the saved Steam prefix {} is no longer valid, looking for one instead",
+                saved_steam_path.display()
+            );
+
+            find_steam_prefix_for_game(&discover_installations(), &game_path)
+        }
+        (None, saved_steam_path) => saved_steam_path,
+    };
+
+    settings.game_path = Some(game_path);
+    settings.steam_path = steam_path;
+    settings.mod_folder_paths.extend(mod_folder_paths);
+
+    Ok(Some(CliOptions {
+        command,
+        settings,
+        modconfig_xml_path: match modconfig_xml_path {
+            Some(modconfig_xml_path) => modconfig_xml_path,
+            None => resolve_modconfig_xml_path().ok_or(FileError::MissingHomeDir)?,
+        },
+        profile,
+        game_version_override,
+    }))
+}
+
+/// Runs the command line interface and gives back the exit code
+pub fn run(args: &[String]) -> i32 {
+    // The only command that doesn't need an installation
+    if args.first().map(String::as_str) == Some("detect") {
+        for installation in discover_installations() {
+            println!(
+                "{}\t{}\t{}",
+                installation.game_path.display(),
+                installation
+                    .workshop_path
+                    .map(|path| path.display().to_string())
+                    .unwrap_or_default(),
+                installation.origin
+            );
+        }
+
+        return 0;
+    }
+
+    if matches!(
+        args.first().map(String::as_str),
+        Some("backups" | "restore")
+    ) {
+        return match run_backup_command(args) {
+            Ok(()) => 0,
+            Err(error) => {
+                eprintln!("Error: {:#}", error);
+                2
+            }
+        };
+    }
+
+    // Fall back to whatever the user interface remembers
+    let settings = Settings::load().unwrap_or_default();
+
+    let options = match parse_arguments(args, settings) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return 0;
+        }
+        Err(error) => {
+            eprintln!("{}\n\n{}", error, USAGE);
+            return 2;
+        }
+    };
+
+    match run_command(options) {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+            2
+        }
+    }
+}
+
+/// Handles listing and restoring backups, neither of which need an installation
+fn run_backup_command(args: &[String]) -> Result<(), Error> {
+    let (number_arg, options) = match args[0].as_str() {
+        "restore" => (args.get(1), args.get(2..).unwrap_or_default()),
+        _ => (None, &args[1..]),
+    };
+    let modconfig_xml_path = match options {
+        [option, path] if option == "--config" => PathBuf::from(path),
+        [] => resolve_modconfig_xml_path().ok_or(FileError::MissingHomeDir)?,
+        _ => return Err(anyhow!("{} only takes --config", args[0])),
+    };
+
+    let backups = list_backups(&modconfig_xml_path)?;
+
+    if args[0] == "backups" {
+        for (number, backup) in backups.iter().enumerate() {
+            println!(
+                "{}\t{} UTC\t{}",
+                number + 1,
+                format_timestamp(backup.created),
+                backup.path.display()
+            );
+        }
+
+        return Ok(());
+    }
+
+    let number: usize = number_arg
+        .ok_or_else(|| anyhow!("restore needs the number of the backup"))?
+        .parse()?;
+    let backup = number
+        .checked_sub(1)
+        .and_then(|index| backups.get(index))
+        .ok_or_else(|| anyhow!("There is no backup number {}", number))?;
+
+    restore_backup(backup, &modconfig_xml_path)?;
+    eprintln!(
+        "Restored the backup from {} UTC to {}",
+        format_timestamp(backup.created),
+        modconfig_xml_path.display()
+    );
+
+    Ok(())
+}
+
+/// Returns if the mod list is free of problems
+fn run_command(options: CliOptions) -> Result<bool, Error> {
+    let mut manager = RimManager {
+        game_version_override: options.game_version_override,
+        ..Default::default()
+    };
+
+    let mut rule_errors = manager.load_rule_dbs();
+    rule_errors.extend(options.settings.apply_without_scanning(&mut manager));
+
+    for error in rule_errors {
+        eprintln!("warning: {}", error);
+    }
+    for error in manager.refresh_metadata()? {
+        eprintln!("warning: {}", error);
+    }
+
+    for (location, broken_mod) in &manager.broken_mods {
+        eprintln!(
+            "warning: the mod in {} is broken: {:#}",
+            location.display(),
+            broken_mod.error
+        );
+    }
+
+    for package_id in manager.duplicate_mods.keys() {
+        if let Some(mod_metadata) = manager.inactive_mod_list.0.get(package_id) {
+            eprintln!(
+                "warning: {} is installed more than once, using the copy in {}",
+                package_id.0,
+                mod_metadata.location.display()
+            );
+        }
+    }
+
+    if options.command == Command::Scan {
+        for (package_id, mod_metadata) in &manager.inactive_mod_list.0 {
+            println!(
+                "{}\t{}\t{}\t{}",
+                package_id.0,
+                mod_metadata.displayable_name,
+                mod_metadata.location.display(),
+                mod_metadata.source
+            );
+        }
+
+        return Ok(true);
+    }
+
+    let mut mod_config_data = read_modconfig_xml_or_default_from(&options.modconfig_xml_path)?;
+
+    let (mod_ordering, version_overrides) = match &options.profile {
+        Some(name) => {
+            let profile = ProfileStore::load()?
+                .profiles
+                .shift_remove(name)
+                .ok_or_else(|| anyhow!("There is no profile named {}", name))?;
+
+            (profile.active_mods, profile.version_overrides)
+        }
+        None => (
+            mod_config_data.active_mods.list.iter().cloned().collect(),
+            ProfileStore::load().unwrap_or_default().version_overrides,
+        ),
+    };
+
+    // Saving would quietly drop these so they count as problems
+    let missing_mods = manager.load_mod_ordering(&mod_ordering);
+
+    for missing_mod in &missing_mods {
+        eprintln!("{} is in the mod list but not installed", missing_mod.0);
+    }
+
+    if matches!(options.command, Command::Sort | Command::Save) {
+        if let Err(error) = manager.active_mod_list.autofix(
+            &manager.mod_rules,
+            &mut manager.inactive_mod_list,
+            &mut manager.mod_list_issue_cache,
+        ) {
+            eprintln!("The mod list could not be sorted. {}", error);
+        }
+    }
+
+    let mut issues: Vec<_> = manager
+        .mod_list_issue_cache
+        .0
+        .iter()
+        .flat_map(|(package_id, problems)| {
+            problems
+                .iter()
+                .map(move |(problem_id, relation)| (package_id, problem_id, relation))
+        })
+        .collect();
+    issues.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    for (package_id, problem_id, relation) in &issues {
+        eprintln!("{}: {:?} {}", package_id.0, relation, problem_id.0);
+    }
+
+    for package_id in manager
+        .active_mod_list
+        .find_unsupported_mods(&version_overrides)
+    {
+        eprintln!(
+            "warning: {} doesn't declare support for RimWorld {}",
+            package_id.0,
+            manager
+                .game_version()
+                .map(|game_version| game_version.major_minor().to_string())
+                .unwrap_or_default()
+        );
+    }
+
+    for (package_id, mod_metadata) in &manager.active_mod_list.0 {
+        if !mod_metadata.unreadable_versions.is_empty() {
+            eprintln!(
+                "warning: {} lists versions that can't be read, anything under them is ignored: {}",
+                package_id.0,
+                mod_metadata.unreadable_versions.join(", ")
+            );
+        }
+    }
+
+    for package_id in manager.active_mod_list.find_mods_without_version_content() {
+        eprintln!(
+            "warning: {} has no folder for RimWorld {}, older content will be loaded",
+            package_id.0,
+            manager
+                .game_version()
+                .map(|game_version| game_version.major_minor().to_string())
+                .unwrap_or_default()
+        );
+    }
+
+    for cycle in manager.mod_rules.find_cycles(&manager.active_mod_list) {
+        eprintln!("circular dependency: {}", format_cycle(&cycle));
+
+        for edge in &cycle {
+            eprintln!("    {}", edge);
+        }
+    }
+
+    if options.command != Command::Check {
+        for package_id in manager.active_mod_list.0.keys() {
+            println!("{}", package_id.0);
+        }
+    }
+
+    let has_core = manager
+        .active_mod_list
+        .0
+        .contains_key(&PackageId("ludeon.rimworld".to_owned()));
+
+    if !has_core {
+        eprintln!("Your mod-list must contain the Core module (ludeon.rimworld)");
+    }
+
+    let is_clean = has_core && issues.is_empty() && missing_mods.is_empty();
+
+    if options.command == Command::Save {
+        if !is_clean {
+            eprintln!("Not saving as the mod list still has problems");
+            return Ok(false);
+        }
+
+        manager.fill_modconfig_xml(&mut mod_config_data);
+        write_modconfig_xml_to(&mod_config_data, &options.modconfig_xml_path)?;
+
+        eprintln!("Saved to {}", options.modconfig_xml_path.display());
+    }
+
+    Ok(is_clean)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporary_dir;
+    use std::fs;
+
+    fn args(raw: &[&str]) -> Vec<String> {
+        raw.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    /// Just enough of an installation to pass the checks
+    fn game_installation(name: &str) -> PathBuf {
+        let game_path = temporary_dir(name);
+        fs::write(game_path.join("Version.txt"), "1.5.4104 rev435").unwrap();
+        fs::create_dir_all(game_path.join("Data")).unwrap();
+        game_path
+    }
+
+    fn parse(raw: &[&str], settings: Settings) -> Result<Option<CliOptions>, Error> {
+        parse_arguments(&args(raw), settings)
+    }
+
+    #[test]
+    fn help_and_no_command_print_the_usage() {
+        assert!(parse(&[], Settings::default()).unwrap().is_none());
+        assert!(parse(&["help"], Settings::default()).unwrap().is_none());
+        assert!(parse(&["check", "--help"], Settings::default())
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn bad_arguments_are_errors() {
+        let error = |raw: &[&str]| {
+            parse(raw, Settings::default())
+                .err()
+                .map(|error| error.to_string())
+        };
+
+        assert_eq!(
+            error(&["frobnicate"]).as_deref(),
+            Some("Unknown command: frobnicate")
+        );
+        assert_eq!(
+            error(&["check", "--loud"]).as_deref(),
+            Some("Unknown option: --loud")
+        );
+        assert_eq!(
+            error(&["check", "--game"]).as_deref(),
+            Some("Missing value for --game")
+        );
+        assert_eq!(
+            error(&["check", "--config", "ModsConfig.xml"]).as_deref(),
+            Some("--game is required")
+        );
+        assert!(error(&["check", "--game-version", "1.5.4104"]).is_some());
+    }
+
+    #[test]
+    fn the_game_path_has_to_be_an_installation() {
+        let not_a_game = temporary_dir("cli-not-a-game");
+
+        assert!(parse(
+            &["check", "--game", &not_a_game.to_string_lossy()],
+            Settings::default()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn options_are_read() {
+        let game_path = game_installation("cli-options");
+        let options = parse(
+            &[
+                "sort",
+                "--game",
+                &game_path.to_string_lossy(),
+                "--config",
+                "ModsConfig.xml",
+                "--profile",
+                "Main",
+                "--game-version",
+                "1.4",
+            ],
+            Settings::default(),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(options.command, Command::Sort);
+        assert_eq!(options.settings.game_path, Some(game_path));
+        assert_eq!(options.modconfig_xml_path, PathBuf::from("ModsConfig.xml"));
+        assert_eq!(options.profile.as_deref(), Some("Main"));
+        assert_eq!(
+            options.game_version_override,
+            Some(GameVersion::parse_major_minor("1.4").unwrap())
+        );
+    }
+
+    #[test]
+    fn the_saved_game_path_is_used_when_none_is_given() {
+        let game_path = game_installation("cli-saved-game");
+        let settings = Settings {
+            game_path: Some(game_path.clone()),
+            ..Default::default()
+        };
+
+        let options = parse(&["check", "--config", "ModsConfig.xml"], settings)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(options.command, Command::Check);
+        assert_eq!(options.settings.game_path, Some(game_path));
+    }
+
+    #[test]
+    fn extra_mod_folders_are_added_to_the_saved_ones() {
+        let game_path = game_installation("cli-mod-folders");
+        let settings = Settings {
+            mod_folder_paths: [PathBuf::from("/saved/mods")].into_iter().collect(),
+            ..Default::default()
+        };
+
+        let options = parse(
+            &[
+                "check",
+                "--game",
+                &game_path.to_string_lossy(),
+                "--config",
+                "ModsConfig.xml",
+                "--mods",
+                "/more/mods",
+            ],
+            settings,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(
+            options
+                .settings
+                .mod_folder_paths
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [PathBuf::from("/saved/mods"), PathBuf::from("/more/mods")]
+        );
+    }
+
+    #[test]
+    fn only_a_given_steam_prefix_has_to_be_valid() {
+        let game_path = game_installation("cli-steam");
+        let not_a_prefix = temporary_dir("cli-not-a-prefix");
+
+        assert!(parse(
+            &[
+                "check",
+                "--game",
+                &game_path.to_string_lossy(),
+                "--config",
+                "ModsConfig.xml",
+                "--steam",
+                &not_a_prefix.to_string_lossy(),
+            ],
+            Settings::default(),
+        )
+        .is_err());
+
+        let settings = Settings {
+            steam_path: Some(not_a_prefix.clone()),
+            ..Default::default()
+        };
+        let options = parse(
+            &[
+                "check",
+                "--game",
+                &game_path.to_string_lossy(),
+                "--config",
+                "ModsConfig.xml",
+            ],
+            settings,
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_ne!(options.settings.steam_path, Some(not_a_prefix));
+    }
+}
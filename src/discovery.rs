@@ -0,0 +1,421 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    does_directory_represent_valid_game_installation, does_directory_represent_valid_steam_prefix,
+    vdf::{read_vdf, VdfValue},
+};
+use homedir::get_my_home;
+use itertools::Itertools;
+
+/// RimWorld's Steam app id
+pub const RIMWORLD_APP_ID: &str = "294100";
+
+/// A RimWorld installation found without the user pointing us at it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiscoveredInstallation {
+    pub game_path: PathBuf,
+    /// The Steam prefix the game came from, if it came from Steam
+    pub steam_path: Option<PathBuf>,
+    /// Where the workshop mods are kept, if there are any
+    pub workshop_path: Option<PathBuf>,
+    /// Where we found it, for showing to the user
+    pub origin: String,
+}
+
+/// Places Steam is usually installed to
+fn steam_prefix_candidates(home: &Path) -> Vec<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let candidates = vec![
+        home.join(".steam").join("steam"),
+        home.join(".steam").join("root"),
+        home.join(".local").join("share").join("Steam"),
+        // Flatpak
+        home.join(".var")
+            .join("app")
+            .join("com.valvesoftware.Steam")
+            .join(".local")
+            .join("share")
+            .join("Steam"),
+        // Snap
+        home.join("snap")
+            .join("steam")
+            .join("common")
+            .join(".local")
+            .join("share")
+            .join("Steam"),
+    ];
+
+    // TODO: Other platforms
+    #[cfg(not(target_os = "linux"))]
+    let candidates = {
+        let _ = home;
+        Vec::new()
+    };
+
+    candidates
+}
+
+/// Places GOG installers and launchers like Heroic usually put the game
+fn drm_free_candidates(home: &Path) -> Vec<(PathBuf, &'static str)> {
+    #[cfg(target_os = "linux")]
+    let candidates = vec![
+        (home.join("GOG Games").join("RimWorld"), "GOG"),
+        (home.join("Games").join("Heroic").join("RimWorld"), "Heroic"),
+        (home.join("Games").join("RimWorld"), "Games folder"),
+        (
+            home.join(".var")
+                .join("app")
+                .join("com.heroicgameslauncher.hgl")
+                .join("Games")
+                .join("Heroic")
+                .join("RimWorld"),
+            "Heroic (Flatpak)",
+        ),
+    ];
+
+    // TODO: Other platforms
+    #[cfg(not(target_os = "linux"))]
+    let candidates = {
+        let _ = home;
+        Vec::new()
+    };
+
+    candidates
+}
+
+/// Every library folder listed in the prefix's libraryfolders.vdf, along with if Steam says RimWorld is in it
+/// The prefix itself is always a library even if the file can't be read
+pub fn find_steam_libraries(steam_prefix: &Path) -> Vec<(PathBuf, bool)> {
+    let mut libraries = vec![(steam_prefix.to_path_buf(), false)];
+
+    let library_file = [
+        steam_prefix.join("steamapps").join("libraryfolders.vdf"),
+        steam_prefix.join("config").join("libraryfolders.vdf"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file());
+
+    let Some(library_file) = library_file else {
+        return libraries;
+    };
+
+    let library_folders = match read_vdf(&library_file) {
+        Ok(vdf) => vdf,
+        Err(error) => {
+            log::warn!("Can't parse {}: {}", library_file.display(), error);
+            return libraries;
+        }
+    };
+
+    let Some(library_folders) = library_folders.get_table("libraryfolders") else {
+        return libraries;
+    };
+
+    for (key, value) in &library_folders.0 {
+        // Entries are numbered, everything else is metadata like contentstatsid
+        if key.parse::<u32>().is_err() {
+            continue;
+        }
+
+        let (path, has_rimworld) = match value {
+            // Old format where the entry is just the path
+            VdfValue::String(path) => (PathBuf::from(path), false),
+            VdfValue::Table(library) => {
+                let Some(path) = library.get_string("path") else {
+                    continue;
+                };
+
+                let has_rimworld = library
+                    .get_table("apps")
+                    .is_some_and(|apps| apps.0.contains_key(RIMWORLD_APP_ID));
+
+                (PathBuf::from(path), has_rimworld)
+            }
+        };
+
+        match libraries.iter_mut().find(|(library, _)| {
+            // Libraries on drives that aren't mounted can't be canonicalized
+            matches!(
+                (library.canonicalize(), path.canonicalize()),
+                (Ok(library), Ok(path)) if library == path
+            ) || *library == path
+        }) {
+            Some(existing) => existing.1 |= has_rimworld,
+            None => libraries.push((path, has_rimworld)),
+        }
+    }
+
+    libraries
+}
+
+/// Where Steam installed the game in a library according to its appmanifest_294100.acf
+/// Falls back to the default folder name if the manifest is missing or broken
+pub fn find_game_in_library(library: &Path) -> Option<PathBuf> {
+    let steamapps = library.join("steamapps");
+    let manifest_path = steamapps.join(format!("appmanifest_{}.acf", RIMWORLD_APP_ID));
+
+    let install_dir = match read_vdf(&manifest_path) {
+        Ok(manifest) => manifest
+            .get_table("AppState")
+            .and_then(|app_state| app_state.get_string("installdir"))
+            .map(ToOwned::to_owned),
+        Err(error) => {
+            if manifest_path.exists() {
+                log::warn!("Can't parse {}: {}", manifest_path.display(), error);
+            }
+
+            None
+        }
+    };
+
+    let game_path = steamapps
+        .join("common")
+        .join(install_dir.as_deref().unwrap_or("RimWorld"));
+
+    game_path.is_dir().then_some(game_path)
+}
+
+/// Every workshop folder for RimWorld across all the prefix's libraries
+pub fn find_workshop_paths(steam_prefix: &Path) -> Vec<PathBuf> {
+    find_steam_libraries(steam_prefix)
+        .into_iter()
+        .map(|(library, _)| workshop_path_for_library(&library))
+        .filter(|path| path.is_dir())
+        .collect()
+}
+
+/// Where workshop mods live in a library
+pub fn workshop_path_for_library(library: &Path) -> PathBuf {
+    library
+        .join("steamapps")
+        .join("workshop")
+        .join("content")
+        .join(RIMWORLD_APP_ID)
+}
+
+/// The Steam prefix of a discovered installation at this game path, if it came from Steam
+pub fn find_steam_prefix_for_game(
+    installations: &[DiscoveredInstallation],
+    game_path: &Path,
+) -> Option<PathBuf> {
+    let game_path = game_path
+        .canonicalize()
+        .unwrap_or_else(|_| game_path.to_owned());
+
+    installations
+        .iter()
+        .find(|installation| {
+            installation
+                .game_path
+                .canonicalize()
+                .unwrap_or_else(|_| installation.game_path.clone())
+                == game_path
+        })
+        .and_then(|installation| installation.steam_path.clone())
+}
+
+/// Looks through the usual places for RimWorld installations
+pub fn discover_installations() -> Vec<DiscoveredInstallation> {
+    let Some(home) = get_my_home().ok().flatten() else {
+        return Vec::new();
+    };
+
+    let mut installations = Vec::new();
+
+    // The same prefix tends to be reachable from several of the candidates through symlinks
+    let steam_prefixes = steam_prefix_candidates(&home)
+        .into_iter()
+        .filter(|path| does_directory_represent_valid_steam_prefix(path))
+        .unique_by(|path| path.canonicalize().unwrap_or_else(|_| path.clone()))
+        .collect::<Vec<_>>();
+
+    for steam_prefix in steam_prefixes {
+        let libraries = find_steam_libraries(&steam_prefix);
+
+        // Workshop content ends up in the library the game is installed to
+        for (library, has_rimworld) in libraries {
+            let Some(game_path) = find_game_in_library(&library) else {
+                if has_rimworld {
+                    log::warn!(
+                        "Steam says RimWorld is in {} but it can't be found there",
+                        library.display()
+                    );
+                }
+
+                continue;
+            };
+
+            if !does_directory_represent_valid_game_installation(&game_path) {
+                log::warn!(
+                    "Steam says RimWorld is in {} but there is no valid installation there",
+                    library.display()
+                );
+                continue;
+            }
+
+            let workshop_path = workshop_path_for_library(&library);
+
+            installations.push(DiscoveredInstallation {
+                game_path,
+                steam_path: Some(steam_prefix.clone()),
+                workshop_path: workshop_path.is_dir().then_some(workshop_path),
+                origin: format!("Steam library {}", library.display()),
+            });
+        }
+    }
+
+    for (game_path, origin) in drm_free_candidates(&home) {
+        if does_directory_represent_valid_game_installation(&game_path) {
+            installations.push(DiscoveredInstallation {
+                game_path,
+                steam_path: None,
+                workshop_path: None,
+                origin: origin.to_owned(),
+            });
+        }
+    }
+
+    installations
+        .into_iter()
+        .unique_by(|installation| {
+            installation
+                .game_path
+                .canonicalize()
+                .unwrap_or_else(|_| installation.game_path.clone())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporary_dir;
+    use std::fs;
+
+    /// A fresh Steam prefix with the given libraryfolders.vdf
+    fn steam_prefix(name: &str, library_folders: &str) -> PathBuf {
+        let steam_prefix = temporary_dir(name);
+        fs::create_dir_all(steam_prefix.join("steamapps")).unwrap();
+        fs::write(
+            steam_prefix.join("steamapps").join("libraryfolders.vdf"),
+            library_folders.replace("PREFIX", &steam_prefix.to_string_lossy()),
+        )
+        .unwrap();
+
+        steam_prefix
+    }
+
+    #[test]
+    fn libraries_are_read_from_the_current_format() {
+        let steam_prefix = steam_prefix(
+            "current",
+            r#""libraryfolders"
+            {
+                "contentstatsid" "123"
+                "0" { "path" "PREFIX" "apps" { "228980" "1" } }
+                "1" { "path" "/mnt/games" "apps" { "294100" "1" } }
+            }"#,
+        );
+
+        assert_eq!(
+            find_steam_libraries(&steam_prefix),
+            [
+                (steam_prefix.clone(), false),
+                (PathBuf::from("/mnt/games"), true)
+            ]
+        );
+
+        fs::remove_dir_all(steam_prefix).unwrap();
+    }
+
+    #[test]
+    fn libraries_are_read_from_the_old_format() {
+        let steam_prefix = steam_prefix(
+            "old",
+            r#""LibraryFolders"
+            {
+                "TimeNextStatsReport" "123"
+                "1" "/mnt/games"
+                "2" "/mnt/more games"
+            }"#,
+        );
+
+        assert_eq!(
+            find_steam_libraries(&steam_prefix),
+            [
+                (steam_prefix.clone(), false),
+                (PathBuf::from("/mnt/games"), false),
+                (PathBuf::from("/mnt/more games"), false)
+            ]
+        );
+
+        fs::remove_dir_all(steam_prefix).unwrap();
+    }
+
+    #[test]
+    fn game_and_workshop_are_found_in_other_libraries() {
+        let steam_prefix = steam_prefix(
+            "libraries",
+            r#""libraryfolders"
+            {
+                "0" { "path" "PREFIX" }
+                "1" { "path" "PREFIX/library" "apps" { "294100" "1" } }
+            }"#,
+        );
+        let library = steam_prefix.join("library");
+        let steamapps = library.join("steamapps");
+        fs::create_dir_all(steamapps.join("common").join("RimWorld Renamed")).unwrap();
+        fs::create_dir_all(workshop_path_for_library(&library)).unwrap();
+        fs::write(
+            steamapps.join("appmanifest_294100.acf"),
+            r#""AppState" { "appid" "294100" "installdir" "RimWorld Renamed" }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            find_game_in_library(&library),
+            Some(steamapps.join("common").join("RimWorld Renamed"))
+        );
+        assert_eq!(find_game_in_library(&steam_prefix), None);
+        assert_eq!(
+            find_workshop_paths(&steam_prefix),
+            [workshop_path_for_library(&library)]
+        );
+
+        fs::remove_dir_all(steam_prefix).unwrap();
+    }
+
+    #[test]
+    fn steam_prefixes_are_found_by_game_path() {
+        let installations = [
+            DiscoveredInstallation {
+                game_path: PathBuf::from("/gog/RimWorld"),
+                steam_path: None,
+                workshop_path: None,
+                origin: "GOG".to_owned(),
+            },
+            DiscoveredInstallation {
+                game_path: PathBuf::from("/steam/steamapps/common/RimWorld"),
+                steam_path: Some(PathBuf::from("/steam")),
+                workshop_path: None,
+                origin: "Steam library /steam".to_owned(),
+            },
+        ];
+
+        assert_eq!(
+            find_steam_prefix_for_game(
+                &installations,
+                Path::new("/steam/steamapps/common/RimWorld")
+            ),
+            Some(PathBuf::from("/steam"))
+        );
+        assert_eq!(
+            find_steam_prefix_for_game(&installations, Path::new("/gog/RimWorld")),
+            None
+        );
+        assert_eq!(
+            find_steam_prefix_for_game(&installations, Path::new("/elsewhere")),
+            None
+        );
+    }
+}
@@ -0,0 +1,108 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+
+/// Something that went wrong while reading or writing the files we manage
+/// Kept apart from [anyhow::Error] so the interface can show the path and the cause separately
+#[derive(Debug)]
+pub enum FileError {
+    /// Without it we can't find ModsConfig.xml
+    MissingHomeDir,
+    ReadGameVersion {
+        path: PathBuf,
+        cause: Error,
+    },
+    ScanFolder {
+        path: PathBuf,
+        cause: Error,
+    },
+    /// The scan thread panicked
+    ScanCrashed,
+    ReadModsConfig {
+        path: PathBuf,
+        cause: Error,
+    },
+    WriteModsConfig {
+        path: PathBuf,
+        cause: Error,
+    },
+    /// Saving would quietly drop these from the list
+    ModsNotInstalled {
+        path: PathBuf,
+        cause: Error,
+    },
+    ReadRuleFolder {
+        path: PathBuf,
+        cause: Error,
+    },
+    /// The rules in it are left out until it is fixed and reloaded
+    ReadRuleFile {
+        path: PathBuf,
+        cause: Error,
+    },
+}
+
+impl FileError {
+    /// What we were trying to do
+    pub fn summary(&self) -> &'static str {
+        match self {
+            FileError::MissingHomeDir => "Can't find the home folder",
+            FileError::ReadGameVersion { .. } => "Can't read the game version",
+            FileError::ScanFolder { .. } => "Can't scan the mod folder",
+            FileError::ScanCrashed => "The scan crashed",
+            FileError::ReadModsConfig { .. } => "Can't read ModsConfig.xml",
+            FileError::WriteModsConfig { .. } => "Can't write ModsConfig.xml",
+            FileError::ModsNotInstalled { .. } => "Mods in ModsConfig.xml are not installed",
+            FileError::ReadRuleFolder { .. } => "Can't read the rule folder",
+            FileError::ReadRuleFile { .. } => "Can't load the rule file",
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            FileError::ReadGameVersion { path, .. }
+            | FileError::ScanFolder { path, .. }
+            | FileError::ReadModsConfig { path, .. }
+            | FileError::WriteModsConfig { path, .. }
+            | FileError::ModsNotInstalled { path, .. }
+            | FileError::ReadRuleFolder { path, .. }
+            | FileError::ReadRuleFile { path, .. } => Some(path),
+            FileError::MissingHomeDir | FileError::ScanCrashed => None,
+        }
+    }
+
+    pub fn cause(&self) -> Option<&Error> {
+        match self {
+            FileError::ReadGameVersion { cause, .. }
+            | FileError::ScanFolder { cause, .. }
+            | FileError::ReadModsConfig { cause, .. }
+            | FileError::WriteModsConfig { cause, .. }
+            | FileError::ModsNotInstalled { cause, .. }
+            | FileError::ReadRuleFolder { cause, .. }
+            | FileError::ReadRuleFile { cause, .. } => Some(cause),
+            FileError::MissingHomeDir | FileError::ScanCrashed => None,
+        }
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary())?;
+
+        if let Some(path) = self.path() {
+            write!(f, " at {}", path.display())?;
+        }
+
+        if let Some(cause) = self.cause() {
+            write!(f, ": {:#}", cause)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The cause is part of the message already, so it isn't given as the source or it would be printed twice
+impl std::error::Error for FileError {}
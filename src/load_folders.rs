@@ -0,0 +1,365 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::Path,
+};
+
+use anyhow::{Context, Error};
+use indexmap::IndexMap;
+use serde::Deserialize;
+
+use crate::{managment::PackageId, version::GameVersion, xml::deserialize_from_xml_with_position};
+
+// Which folders of a mod RimWorld actually loads
+// Either spelled out per version in LoadFolders.xml or guessed from the 1.4/, Common/ layout when there is none
+
+/// One <li> of LoadFolders.xml
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct LoadFolder {
+    /// Relative to the mod folder, / being the mod folder itself
+    #[serde(rename = "$text", default)]
+    pub path: String,
+    /// Loaded if any of these are active
+    #[serde(rename = "@IfModActive", default)]
+    if_mod_active: Option<String>,
+    /// Loaded if all of these are active
+    #[serde(rename = "@IfModActiveAll", default)]
+    if_mod_active_all: Option<String>,
+    /// Loaded if none of these are active
+    #[serde(rename = "@IfModNotActive", default)]
+    if_mod_not_active: Option<String>,
+}
+
+/// The attributes are comma separated packageIds with whatever spacing the author felt like
+fn split_package_ids(raw: &Option<String>) -> Vec<PackageId> {
+    raw.iter()
+        .flat_map(|raw| raw.split(','))
+        .map(|package_id| PackageId(package_id.trim().to_lowercase()))
+        .filter(|package_id| !package_id.0.is_empty())
+        .collect()
+}
+
+impl LoadFolder {
+    pub fn should_load(&self, is_active: impl Fn(&PackageId) -> bool) -> bool {
+        let any_active = split_package_ids(&self.if_mod_active);
+        let all_active = split_package_ids(&self.if_mod_active_all);
+        let none_active = split_package_ids(&self.if_mod_not_active);
+
+        (any_active.is_empty() || any_active.iter().any(&is_active))
+            && all_active.iter().all(&is_active)
+            && !none_active.iter().any(&is_active)
+    }
+
+    /// The conditions as written, for showing next to the folder
+    pub fn conditions(&self) -> Vec<String> {
+        [
+            ("If any active", &self.if_mod_active),
+            ("If all active", &self.if_mod_active_all),
+            ("If none active", &self.if_mod_not_active),
+        ]
+        .into_iter()
+        .filter_map(|(name, raw)| Some(format!("{}: {}", name, raw.as_ref()?.trim())))
+        .collect()
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LoadFolderList {
+    #[serde(default, rename = "li")]
+    list: Vec<LoadFolder>,
+}
+
+/// LoadFolders.xml, which tells RimWorld which folders of a mod to load for each version
+#[derive(Debug, Clone, Default)]
+pub struct LoadFolders {
+    pub by_version: BTreeMap<GameVersion, Vec<LoadFolder>>,
+    /// Used when nothing is listed for the version
+    pub default: Option<Vec<LoadFolder>>,
+}
+
+impl LoadFolders {
+    /// Versions with at least one folder listed, an empty <v1.5/> doesn't count
+    fn listed_versions(&self) -> impl Iterator<Item = (&GameVersion, &Vec<LoadFolder>)> {
+        self.by_version
+            .iter()
+            .filter(|(_, folders)| !folders.is_empty())
+    }
+
+    /// The list RimWorld picks for a version, falling back to the closest older version with folders then the default one
+    fn folders_for_version(&self, version: GameVersion) -> Option<&Vec<LoadFolder>> {
+        let version = version.major_minor();
+
+        self.listed_versions()
+            .filter(|(listed_version, _)| **listed_version <= version)
+            .last()
+            .map(|(_, folders)| folders)
+            .or(self.default.as_ref())
+            .filter(|folders| !folders.is_empty())
+    }
+}
+
+/// Gives back None if the mod doesn't have a LoadFolders.xml
+pub fn read_load_folders_xml(mod_folder: &Path) -> Result<Option<LoadFolders>, Error> {
+    let load_folders_location = mod_folder.join("LoadFolders.xml");
+
+    if !load_folders_location.is_file() {
+        return Ok(None);
+    }
+
+    let load_folders_data =
+        fs::read(load_folders_location).context("Can't read LoadFolders.xml")?;
+    let load_folders_string = String::from_utf8(load_folders_data)?;
+    // The tags are the versions, like <v1.4>, so there is no fixed structure to deserialize into
+    let raw: IndexMap<String, LoadFolderList> =
+        deserialize_from_xml_with_position(&load_folders_string)
+            .context("Can't parse LoadFolders.xml")?;
+
+    let mut load_folders = LoadFolders::default();
+
+    for (tag, folders) in raw {
+        if tag.eq_ignore_ascii_case("default") {
+            load_folders.default = Some(folders.list);
+            continue;
+        }
+
+        match GameVersion::parse_major_minor(&tag) {
+            Ok(version) => {
+                load_folders.by_version.insert(version, folders.list);
+            }
+            Err(error) => log::warn!(
+                "Ignoring <{}> in the LoadFolders.xml of {}: {}",
+                tag,
+                mod_folder.display(),
+                error
+            ),
+        }
+    }
+
+    Ok(Some(load_folders))
+}
+
+/// How a mod's content is split into folders, found during the scan
+#[derive(Debug, Clone, Default)]
+pub struct ContentLayout {
+    pub load_folders: Option<LoadFolders>,
+    /// Top level folders named after a version, like 1.4, with their name as written
+    pub version_folders: BTreeMap<GameVersion, String>,
+    pub has_common_folder: bool,
+}
+
+impl ContentLayout {
+    pub fn find(mod_folder: &Path) -> Self {
+        let load_folders = read_load_folders_xml(mod_folder).unwrap_or_else(|error| {
+            log::warn!(
+                "The mod in {} has a broken LoadFolders.xml: {:#}",
+                mod_folder.display(),
+                error
+            );
+            None
+        });
+
+        let mut version_folders = BTreeMap::new();
+        let mut has_common_folder = false;
+
+        for entry in mod_folder.read_dir().into_iter().flatten().flatten() {
+            if !entry.path().is_dir() {
+                continue;
+            }
+
+            let name = entry.file_name().to_string_lossy().to_string();
+
+            if name.eq_ignore_ascii_case("Common") {
+                has_common_folder = true;
+            } else if let Ok(version) = GameVersion::parse_major_minor(&name) {
+                version_folders.insert(version, name);
+            }
+        }
+
+        Self {
+            load_folders,
+            version_folders,
+            has_common_folder,
+        }
+    }
+
+    /// The folders RimWorld will load for the version and active mods, in the order they are loaded
+    /// Later ones override files of the same name in earlier ones
+    pub fn folders_to_load(
+        &self,
+        version: GameVersion,
+        is_active: impl Fn(&PackageId) -> bool,
+    ) -> Vec<LoadFolder> {
+        if let Some(folders) = self
+            .load_folders
+            .as_ref()
+            .and_then(|load_folders| load_folders.folders_for_version(version))
+        {
+            return folders
+                .iter()
+                .filter(|folder| folder.should_load(&is_active))
+                .cloned()
+                .collect();
+        }
+
+        // Without a LoadFolders.xml RimWorld loads the mod folder, Common and the folder for the version
+        let version = version.major_minor();
+        let mut paths = vec!["/".to_owned()];
+
+        if self.has_common_folder {
+            paths.push("Common".to_owned());
+        }
+
+        // The newest folder that isn't for a newer version than the game
+        if let Some((_, name)) = self.version_folders.range(..=version).next_back() {
+            paths.push(name.clone());
+        }
+
+        paths
+            .into_iter()
+            .map(|path| LoadFolder {
+                path,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// Versions with their own folder or their own list in LoadFolders.xml
+    pub fn content_versions(&self) -> BTreeSet<GameVersion> {
+        self.version_folders
+            .keys()
+            .chain(
+                self.load_folders
+                    .iter()
+                    .flat_map(|load_folders| load_folders.listed_versions())
+                    .map(|(version, _)| version),
+            )
+            .copied()
+            .collect()
+    }
+
+    /// If the content is split by version but there is nothing for this one
+    /// RimWorld then loads the folders of an older version, which tends to go badly
+    pub fn lacks_content_for_version(&self, version: GameVersion) -> bool {
+        let content_versions = self.content_versions();
+
+        !content_versions.is_empty() && !content_versions.contains(&version.major_minor())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporary_dir;
+
+    fn temporary_mod_folder(name: &str, load_folders_xml: &str) -> std::path::PathBuf {
+        let path = temporary_dir(name);
+        fs::write(path.join("LoadFolders.xml"), load_folders_xml).unwrap();
+        path
+    }
+
+    fn version(raw: &str) -> GameVersion {
+        raw.parse().unwrap()
+    }
+
+    fn paths(folders: &[LoadFolder]) -> Vec<&str> {
+        folders.iter().map(|folder| folder.path.as_str()).collect()
+    }
+
+    #[test]
+    fn empty_version_lists_fall_back_to_the_closest_older_one() {
+        let mod_folder = temporary_mod_folder(
+            "load-folders-fallback",
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<loadFolders>
+  <v1.3>
+    <li>/</li>
+    <li>1.3</li>
+  </v1.3>
+  <v1.4>
+    <li>/</li>
+    <li>1.4</li>
+  </v1.4>
+  <v1.5 />
+  <default>
+    <li>Default</li>
+  </default>
+</loadFolders>"#,
+        );
+
+        let content_layout = ContentLayout::find(&mod_folder);
+        fs::remove_dir_all(&mod_folder).unwrap();
+
+        assert_eq!(
+            paths(&content_layout.folders_to_load(version("1.5.4104"), |_| false)),
+            ["/", "1.4"]
+        );
+        assert_eq!(
+            paths(&content_layout.folders_to_load(version("1.2"), |_| false)),
+            ["Default"]
+        );
+        assert!(content_layout.lacks_content_for_version(version("1.5")));
+        assert!(!content_layout.lacks_content_for_version(version("1.4")));
+    }
+
+    #[test]
+    fn conditional_folders_follow_the_active_mods() {
+        let mod_folder = temporary_mod_folder(
+            "load-folders-conditions",
+            r#"<loadFolders>
+  <v1.5>
+    <li>/</li>
+    <li IfModActive="Ludeon.RimWorld.Royalty, ludeon.rimworld.ideology">Dlc</li>
+    <li IfModActiveAll="a.one,a.two">Both</li>
+    <li IfModNotActive="a.one">WithoutOne</li>
+  </v1.5>
+</loadFolders>"#,
+        );
+
+        let content_layout = ContentLayout::find(&mod_folder);
+        fs::remove_dir_all(&mod_folder).unwrap();
+
+        let load_with = |active: &[&str]| {
+            paths(
+                &content_layout.folders_to_load(version("1.5"), |package_id| {
+                    active.contains(&package_id.0.as_str())
+                }),
+            )
+            .into_iter()
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(load_with(&[]), ["/", "WithoutOne"]);
+        assert_eq!(
+            load_with(&["ludeon.rimworld.royalty"]),
+            ["/", "Dlc", "WithoutOne"]
+        );
+        assert_eq!(load_with(&["a.one"]), ["/"]);
+        assert_eq!(load_with(&["a.one", "a.two"]), ["/", "Both"]);
+    }
+
+    #[test]
+    fn only_major_minor_folders_are_version_folders() {
+        let mod_folder = temporary_dir("load-folders-version-folders");
+
+        for folder in ["1", "1.4", "v1.5", "Common", "Textures"] {
+            fs::create_dir_all(mod_folder.join(folder)).unwrap();
+        }
+
+        let content_layout = ContentLayout::find(&mod_folder);
+        fs::remove_dir_all(&mod_folder).unwrap();
+
+        assert_eq!(
+            content_layout.version_folders,
+            BTreeMap::from([
+                (version("1.4"), "1.4".to_owned()),
+                (version("1.5"), "v1.5".to_owned()),
+            ])
+        );
+        assert!(content_layout.has_common_folder);
+        assert_eq!(
+            paths(&content_layout.folders_to_load(version("1.3"), |_| false)),
+            ["/", "Common"]
+        );
+    }
+}
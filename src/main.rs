@@ -1,17 +1,27 @@
+mod backups;
+mod cli;
+mod discovery;
+mod errors;
+mod load_folders;
 mod managment;
+mod profiles;
+mod scanning;
+mod settings;
 mod ui;
+mod vdf;
+mod version;
+mod watcher;
 mod xml;
 
-use anyhow::Error;
-
-use std::{fs, path::Path, str::FromStr};
+use discovery::discover_installations;
+use homedir::get_my_home;
+use profiles::ProfileStore;
+use settings::Settings;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
 use ui::RimManager;
-use versions::Version;
-
-pub fn parse_game_version(raw: &str) -> Result<Version, Error> {
-    let version = raw.split(' ').next().unwrap();
-    Ok(Version::from_str(version).unwrap())
-}
 
 /// Basic check for if this game directory is valid
 pub fn does_directory_represent_valid_game_installation(game_dir: &Path) -> bool {
@@ -23,9 +33,67 @@ pub fn does_directory_represent_valid_steam_prefix(steam_dir: &Path) -> bool {
     steam_dir.is_dir() && steam_dir.join("steamapps").is_dir()
 }
 
+/// Where rimmanager keeps its own files
+pub fn resolve_config_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let base_path = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| Some(get_my_home().ok()??.join(".config")))?;
+
+    #[cfg(target_os = "macos")]
+    let base_path = get_my_home()
+        .ok()??
+        .join("Library")
+        .join("Application Support");
+
+    #[cfg(target_os = "windows")]
+    let base_path = get_my_home().ok()??.join("AppData").join("Roaming");
+
+    Some(base_path.join("rimmanager"))
+}
+
+/// Where rimmanager keeps files it can rebuild if they get deleted
+pub fn resolve_cache_dir() -> Option<PathBuf> {
+    #[cfg(target_os = "linux")]
+    let base_path = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| Some(get_my_home().ok()??.join(".cache")))?;
+
+    #[cfg(target_os = "macos")]
+    let base_path = get_my_home().ok()??.join("Library").join("Caches");
+
+    #[cfg(target_os = "windows")]
+    let base_path = get_my_home().ok()??.join("AppData").join("Local");
+
+    Some(base_path.join("rimmanager"))
+}
+
+/// Where users can drop their own .ruledb files
+pub fn resolve_rule_dir() -> Option<PathBuf> {
+    Some(resolve_config_dir()?.join("rules"))
+}
+
+/// A fresh empty folder for tests to write into, named so parallel test runs don't collide
+#[cfg(test)]
+pub fn temporary_dir(name: &str) -> PathBuf {
+    let path =
+        std::env::temp_dir().join(format!("rimmanager-test-{}-{}", std::process::id(), name));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
 fn main() {
     env_logger::init();
 
+    // Any arguments means we are running headless
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if !args.is_empty() {
+        std::process::exit(cli::run(&args));
+    }
+
     let options = eframe::NativeOptions {
         vsync: true,
         follow_system_theme: true,
@@ -71,7 +139,30 @@ fn main() {
                 }
             }
 
-            Box::<RimManager>::default()
+            let mut manager = RimManager::default();
+            let rule_errors = manager.load_rule_dbs();
+            manager.notifications.extend(rule_errors);
+
+            match ProfileStore::load() {
+                Ok(profiles) => {
+                    manager.saved_profiles = profiles.clone();
+                    manager.profiles = profiles;
+                }
+                Err(error) => log::warn!("Can't load the profiles: {}", error),
+            }
+
+            match Settings::load() {
+                Ok(settings) => {
+                    let rule_errors = settings.apply(&mut manager);
+                    manager.notifications.extend(rule_errors);
+                    manager.saved_settings = settings;
+                }
+                Err(error) => log::warn!("Can't load the settings: {}", error),
+            }
+
+            manager.discovered_installations = discover_installations();
+
+            Box::new(manager)
         }),
     )
     .unwrap();
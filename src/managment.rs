@@ -1,8 +1,11 @@
+use crate::{load_folders::ContentLayout, xml::ModDependencyInfo};
 use indexmap::IndexMap;
+use itertools::Itertools;
 use serde::{Deserialize, Deserializer, Serialize};
 use std::{
-    collections::HashMap,
-    fs,
+    cmp::Reverse,
+    collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque},
+    fmt, fs,
     hash::Hash,
     path::{Path, PathBuf},
 };
@@ -37,108 +40,571 @@ pub struct ModRules {
     pub rules: HashMap<PackageId, ModRelation>,
 }
 
-impl ModRules {
-    pub fn merge(&mut self, other: Self) {
-        self.start_anchor = other.start_anchor;
-        self.end_anchor = other.end_anchor;
-        self.rules.extend(other.rules);
-    }
-}
-
 #[derive(Default)]
 pub struct ModListIssueCache(pub HashMap<PackageId, HashMap<PackageId, ModRelation>>);
 
+/// Where a mod was installed from
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ModSource {
+    /// Core and the DLCs in the game's Data folder
+    Official,
+    /// The game's Mods folder
+    #[default]
+    Local,
+    /// Subscribed on the Steam workshop
+    Workshop,
+    /// One of the extra mod folders the user added
+    Custom,
+}
+
+impl ModSource {
+    pub const ALL: [ModSource; 4] = [
+        ModSource::Official,
+        ModSource::Local,
+        ModSource::Workshop,
+        ModSource::Custom,
+    ];
+}
+
+impl fmt::Display for ModSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModSource::Official => write!(f, "Core/DLC"),
+            ModSource::Local => write!(f, "Local"),
+            ModSource::Workshop => write!(f, "Workshop"),
+            ModSource::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct CondensedModMetadata {
     pub displayable_name: String,
     pub location: PathBuf,
+    pub source: ModSource,
     pub description: String,
+    /// Versions of RimWorld the About.xml lists, empty for the base game files as they don't list any
+    pub supported_versions: Vec<String>,
+    /// If the About.xml lists the version of the game we are managing
+    pub supports_game_version: bool,
+    /// Versions in the About.xml that couldn't be read, whatever is listed under them is ignored
+    pub unreadable_versions: Vec<String>,
+    /// False when the mod has folders for other versions but not this one, whatever the About.xml claims
+    pub has_content_for_game_version: bool,
+    /// The modDependencies for the game version, kept for the names and workshop links of ones that aren't installed
+    pub dependencies: Vec<ModDependencyInfo>,
+    /// Sorted, from both author and authors
+    pub authors: Vec<String>,
+    pub mod_version: Option<String>,
+    pub url: Option<String>,
+    /// Steam workshop id
+    pub published_file_id: Option<String>,
+    /// Found during the scan so the info panel doesn't go looking for them every frame
+    pub preview_path: Option<PathBuf>,
+    pub icon_path: Option<PathBuf>,
+    /// Which folders get loaded depends on the active mods so only the layout is kept
+    pub content_layout: ContentLayout,
 }
 
-#[derive(Default)]
+/// How another mod relates to the one being looked at
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RelationKind {
+    DependsOn,
+    NeededBy,
+    LoadsBefore,
+    LoadsAfter,
+    IncompatibleWith,
+}
+
+impl fmt::Display for RelationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RelationKind::DependsOn => write!(f, "Depends on"),
+            RelationKind::NeededBy => write!(f, "Needed by"),
+            RelationKind::LoadsBefore => write!(f, "Loads before"),
+            RelationKind::LoadsAfter => write!(f, "Loads after"),
+            RelationKind::IncompatibleWith => write!(f, "Incompatible with"),
+        }
+    }
+}
+
+/// A rule involving a mod, no matter which side declared it
+pub struct ModRelationEntry {
+    pub kind: RelationKind,
+    pub other_package_id: PackageId,
+    /// The mod whose rules this came from
+    pub declared_by: PackageId,
+    /// Where the rule was written down
+    pub source: ModdbType,
+}
+
+/// What activating a mod brings along with it
+pub struct ActivationPlan {
+    /// The mod being activated
+    pub package_id: PackageId,
+    /// Inactive mods it depends on, dependencies of dependencies first
+    pub dependencies: Vec<PackageId>,
+    /// Dependencies that aren't installed, along with the mod that wants them
+    pub missing: Vec<(PackageId, PackageId)>,
+    /// Dependencies that don't support the game version and weren't allowed in the profile
+    pub unsupported: Vec<PackageId>,
+}
+
+/// What deactivating a mod takes down with it
+pub struct DeactivationPlan {
+    /// The mod being deactivated
+    pub package_id: PackageId,
+    /// Active mods that depend on it, directly or through other dependents, in list order
+    pub dependents: Vec<PackageId>,
+}
+
+#[derive(Default, Clone)]
 pub struct ModList(pub IndexMap<PackageId, CondensedModMetadata>);
 
+/// Why a mod list couldn't be sorted
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SortError {
+    /// A mod in the list depends on a mod that isn't installed
+    MissingDependency {
+        package_id: PackageId,
+        dependency: PackageId,
+    },
+    /// Two mods in the list refuse to be loaded together
+    Incompatible {
+        package_id: PackageId,
+        other_package_id: PackageId,
+    },
+    /// The rules contradict each other so no order can satisfy them
+    /// Holds every cycle found, each as the chain of rules that form it
+    CircularDependency(Vec<Vec<LoadOrderEdge>>),
+}
+
+impl fmt::Display for SortError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortError::MissingDependency {
+                package_id,
+                dependency,
+            } => write!(
+                f,
+                "{} depends on {} which is not installed",
+                package_id.0, dependency.0
+            ),
+            SortError::Incompatible {
+                package_id,
+                other_package_id,
+            } => write!(
+                f,
+                "{} is incompatible with {}",
+                package_id.0, other_package_id.0
+            ),
+            SortError::CircularDependency(cycles) => {
+                write!(f, "There is a circular dependency")?;
+
+                for cycle in cycles {
+                    write!(f, "\n\n{}", format_cycle(cycle))?;
+
+                    for edge in cycle {
+                        write!(f, "\n    {}", edge)?;
+                    }
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for SortError {}
+
+/// What made a load order requirement exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadOrderRule {
+    Relation(ModRelation),
+    /// The mod must be above every mod that isn't a start anchor
+    StartAnchor,
+    /// The mod must be below every mod that isn't an end anchor
+    EndAnchor,
+}
+
+/// A requirement that one mod in a list loads before another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadOrderEdge {
+    pub before: PackageId,
+    pub after: PackageId,
+    /// The mod whose rules this came from
+    pub declared_by: PackageId,
+    pub rule: LoadOrderRule,
+    /// Where the rule was written down
+    pub source: ModdbType,
+}
+
+impl fmt::Display for LoadOrderEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let other = if self.declared_by == self.before {
+            &self.after
+        } else {
+            &self.before
+        };
+
+        match &self.rule {
+            LoadOrderRule::Relation(relation) => write!(
+                f,
+                "{} → {}: {} declares {:?} {} in {}",
+                self.before.0, self.after.0, self.declared_by.0, relation, other.0, self.source
+            ),
+            LoadOrderRule::StartAnchor => write!(
+                f,
+                "{} → {}: {} is a start anchor in {}",
+                self.before.0, self.after.0, self.declared_by.0, self.source
+            ),
+            LoadOrderRule::EndAnchor => write!(
+                f,
+                "{} → {}: {} is an end anchor in {}",
+                self.before.0, self.after.0, self.declared_by.0, self.source
+            ),
+        }
+    }
+}
+
+/// Formats a cycle as a chain like A → B → C → A
+pub fn format_cycle(cycle: &[LoadOrderEdge]) -> String {
+    cycle
+        .iter()
+        .map(|edge| edge.before.0.as_str())
+        .chain(cycle.first().map(|edge| edge.before.0.as_str()))
+        .join(" → ")
+}
+
 impl ModList {
+    /// Sorts the list so every rule is satisfied, pulling in installed dependencies from the inactive list
+    /// The current order is used as a tie-break so as few mods as possible move
     pub fn autofix(
         &mut self,
         db: &ModRuleDb,
         inactive_list: &mut ModList,
         issue_cache: &mut ModListIssueCache,
-    ) -> bool {
-        let mut infinite_loop_checker = 100 + self.0.len() + inactive_list.0.len();
-        let mut movement_reverse_tracker = HashMap::new();
-        let mut index = 0;
+    ) -> Result<(), SortError> {
+        // Sorted on copies so a list that can't be fixed is left exactly as it was
+        let mut sorted_list = self.clone();
+        let mut sorted_inactive_list = inactive_list.clone();
+        let result = sorted_list.sort(db, &mut sorted_inactive_list);
 
-        while !issue_cache.0.is_empty() {
-            let package_id = self.0.get_index(index).unwrap().0.clone();
+        if result.is_ok() {
+            *self = sorted_list;
+            *inactive_list = sorted_inactive_list;
+        }
 
-            if let Some(issues) = issue_cache.0.get(&package_id) {
-                let (problem_package_id, relation) = issues.iter().next().unwrap();
+        self.find_list_issues(db, issue_cache);
+        result
+    }
 
-                log::info!(
-                    "Solving conflict for mod: {} and {}",
-                    package_id.0,
-                    problem_package_id.0
-                );
+    fn sort(&mut self, db: &ModRuleDb, inactive_list: &mut ModList) -> Result<(), SortError> {
+        self.activate_dependencies(db, inactive_list)?;
 
-                // Exit early as there is probably a circular dependency
-                if infinite_loop_checker == 0 {
-                    return false;
-                }
-
-                match relation {
-                    // This ugly thing is to prevent indirect circular dependencies with 3 or more adjacent mods
-                    ModRelation::Before | ModRelation::After => {
-                        let movement_reverse_tracker = movement_reverse_tracker
-                            .entry((package_id.clone(), problem_package_id.clone()))
-                            .or_insert(false);
-
-                        if *movement_reverse_tracker {
-                            self.0.move_index(
-                                self.0.get_index_of(&package_id).unwrap(),
-                                self.0.get_index_of(problem_package_id).unwrap(),
-                            );
-                        } else {
-                            self.0.move_index(
-                                self.0.get_index_of(problem_package_id).unwrap(),
-                                self.0.get_index_of(&package_id).unwrap(),
-                            );
-                        }
+        // Incompatibilities can't be solved by moving things around
+        // The rules are in hash maps so the first one is picked by name to always report the same one
+        let incompatibility = db
+            .rules_for_list(self)
+            .flat_map(|(package_id, rules)| {
+                rules
+                    .rules
+                    .iter()
+                    .filter(|(other_package_id, relation)| {
+                        matches!(relation, ModRelation::Incompatibility)
+                            && self.0.contains_key(*other_package_id)
+                    })
+                    .map(move |(other_package_id, _)| (package_id, other_package_id))
+            })
+            .min();
+
+        if let Some((package_id, other_package_id)) = incompatibility {
+            return Err(SortError::Incompatible {
+                package_id: package_id.clone(),
+                other_package_id: other_package_id.clone(),
+            });
+        }
 
-                        *movement_reverse_tracker = !*movement_reverse_tracker;
+        // Kahn's algorithm where the mod closest to the top of the list always goes first
+        let mut successors = vec![Vec::new(); self.0.len()];
+        let mut predecessor_count = vec![0; self.0.len()];
+
+        for edge in db.load_order_edges(self) {
+            let before = self.0.get_index_of(&edge.before).unwrap();
+            let after = self.0.get_index_of(&edge.after).unwrap();
+
+            successors[before].push(after);
+            predecessor_count[after] += 1;
+        }
+
+        let mut ready: BinaryHeap<_> = predecessor_count
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count == 0)
+            .map(|(index, _)| Reverse(index))
+            .collect();
+        let mut order = Vec::with_capacity(self.0.len());
+
+        while let Some(Reverse(index)) = ready.pop() {
+            order.push(index);
+
+            for successor in &successors[index] {
+                predecessor_count[*successor] -= 1;
+
+                if predecessor_count[*successor] == 0 {
+                    ready.push(Reverse(*successor));
+                }
+            }
+        }
+
+        if order.len() != self.0.len() {
+            return Err(SortError::CircularDependency(db.find_cycles(self)));
+        }
+
+        let mut old_list = std::mem::take(&mut self.0)
+            .into_iter()
+            .map(Some)
+            .collect::<Vec<_>>();
+
+        self.0 = order
+            .into_iter()
+            .map(|index| old_list[index].take().unwrap())
+            .collect();
+
+        Ok(())
+    }
+
+    /// Moves every installed dependency of the list into it, following dependencies of dependencies
+    /// Each one goes right above the highest mod that needs it so the sort doesn't have to move anything else
+    fn activate_dependencies(
+        &mut self,
+        db: &ModRuleDb,
+        inactive_list: &mut ModList,
+    ) -> Result<(), SortError> {
+        loop {
+            let mut missing = Vec::new();
+
+            for (package_id, rules) in db.rules_for_list(self) {
+                for (dependency, relation) in &rules.rules {
+                    if matches!(relation, ModRelation::Dependency)
+                        && !self.0.contains_key(dependency)
+                    {
+                        missing.push((package_id.clone(), dependency.clone()));
                     }
-                    ModRelation::Dependency => {
-                        if inactive_list.0.contains_key(problem_package_id) {
-                            self.0.insert(
-                                problem_package_id.clone(),
-                                inactive_list.0.shift_remove(problem_package_id).unwrap(),
-                            );
-                        } else {
-                            return false;
-                        }
+                }
+            }
+
+            if missing.is_empty() {
+                return Ok(());
+            }
+
+            // Top of the list first, and the same order every time so the same dependency gets reported
+            missing.sort_by_key(|(package_id, dependency)| {
+                (self.0.get_index_of(package_id), dependency.clone())
+            });
+
+            for (package_id, dependency) in missing {
+                if self.0.contains_key(&dependency) {
+                    continue;
+                }
+
+                match inactive_list.0.shift_remove(&dependency) {
+                    Some(mod_metadata) => {
+                        log::info!(
+                            "Activating {} as {} depends on it",
+                            dependency.0,
+                            package_id.0
+                        );
+
+                        let (index, _) = self.0.insert_full(dependency, mod_metadata);
+                        self.0
+                            .move_index(index, self.0.get_index_of(&package_id).unwrap());
                     }
-                    ModRelation::Incompatibility => {
-                        return false;
+                    None => {
+                        return Err(SortError::MissingDependency {
+                            package_id,
+                            dependency,
+                        })
                     }
                 }
+            }
+        }
+    }
 
-                self.find_list_issues(db, issue_cache);
-            } else {
-                index += 1;
-                infinite_loop_checker -= 1;
+    /// Works out what activating a mod from this inactive list takes, following dependencies of dependencies
+    pub fn plan_activation(
+        &self,
+        db: &ModRuleDb,
+        active_list: &ModList,
+        package_id: &PackageId,
+        version_overrides: &BTreeSet<PackageId>,
+    ) -> ActivationPlan {
+        fn visit(
+            db: &ModRuleDb,
+            active_list: &ModList,
+            inactive_list: &ModList,
+            package_id: &PackageId,
+            visited: &mut HashSet<PackageId>,
+            plan: &mut ActivationPlan,
+        ) {
+            for dependency in db.dependencies_of(package_id) {
+                if active_list.0.contains_key(dependency) || !visited.insert(dependency.clone()) {
+                    continue;
+                }
 
-                if index >= self.0.len() {
-                    index = 0;
+                if inactive_list.0.contains_key(dependency) {
+                    // Whatever it needs goes in before it
+                    visit(db, active_list, inactive_list, dependency, visited, plan);
+                    plan.dependencies.push(dependency.clone());
+                } else {
+                    plan.missing.push((package_id.clone(), dependency.clone()));
                 }
             }
         }
 
-        true
+        let mut plan = ActivationPlan {
+            package_id: package_id.clone(),
+            dependencies: Vec::new(),
+            missing: Vec::new(),
+            unsupported: Vec::new(),
+        };
+        let mut visited = HashSet::from([package_id.clone()]);
+
+        visit(db, active_list, self, package_id, &mut visited, &mut plan);
+
+        plan.unsupported = plan
+            .dependencies
+            .iter()
+            .filter(|dependency| {
+                !self.0[*dependency].supports_game_version
+                    && !version_overrides.contains(*dependency)
+            })
+            .cloned()
+            .collect();
+
+        plan
+    }
+
+    /// Moves the mod in the plan and its dependencies out of the inactive list and puts them where the rules want them
+    pub fn activate(&mut self, db: &ModRuleDb, inactive_list: &mut ModList, plan: &ActivationPlan) {
+        // Mods go in before their dependencies so each dependency can be put above whatever needs it
+        for package_id in [&plan.package_id]
+            .into_iter()
+            .chain(plan.dependencies.iter().rev())
+        {
+            if let Some(mod_metadata) = inactive_list.0.shift_remove(package_id) {
+                self.insert_in_place(db, package_id.clone(), mod_metadata);
+            }
+        }
+    }
+
+    /// Works out which mods in this active list break when a mod leaves it, following dependents of dependents
+    pub fn plan_deactivation(&self, db: &ModRuleDb, package_id: &PackageId) -> DeactivationPlan {
+        let mut leaving = HashSet::from([package_id.clone()]);
+
+        loop {
+            let broken: Vec<_> = self
+                .0
+                .keys()
+                .filter(|other_package_id| {
+                    !leaving.contains(*other_package_id)
+                        && db
+                            .dependencies_of(other_package_id)
+                            .into_iter()
+                            .any(|dependency| leaving.contains(dependency))
+                })
+                .cloned()
+                .collect();
+
+            if broken.is_empty() {
+                break;
+            }
+
+            leaving.extend(broken);
+        }
+
+        DeactivationPlan {
+            package_id: package_id.clone(),
+            dependents: self
+                .0
+                .keys()
+                .filter(|other_package_id| {
+                    *other_package_id != package_id && leaving.contains(*other_package_id)
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// Moves the mod in the plan into the inactive list, along with its dependents if asked to
+    pub fn deactivate(
+        &mut self,
+        inactive_list: &mut ModList,
+        plan: &DeactivationPlan,
+        with_dependents: bool,
+    ) {
+        let dependents = if with_dependents {
+            plan.dependents.as_slice()
+        } else {
+            &[]
+        };
+
+        for package_id in [&plan.package_id].into_iter().chain(dependents) {
+            if let Some(mod_metadata) = self.0.shift_remove(package_id) {
+                inactive_list.0.insert(package_id.clone(), mod_metadata);
+            }
+        }
+    }
+
+    /// Adds a mod as far down the list as it can go without loading after something it must load before
+    /// If the rules can't all be met it goes right after the last mod it must load after
+    pub fn insert_in_place(
+        &mut self,
+        db: &ModRuleDb,
+        package_id: PackageId,
+        mod_metadata: CondensedModMetadata,
+    ) {
+        let (index, _) = self.0.insert_full(package_id.clone(), mod_metadata);
+
+        let mut earliest = 0;
+        let mut latest = index;
+
+        for edge in db.load_order_edges(self) {
+            if edge.after == package_id {
+                earliest = earliest.max(self.0.get_index_of(&edge.before).unwrap() + 1);
+            } else if edge.before == package_id {
+                latest = latest.min(self.0.get_index_of(&edge.after).unwrap());
+            }
+        }
+
+        self.0.move_index(index, earliest.max(latest));
+    }
+
+    /// Mods that don't declare support for the game version and weren't allowed anyway
+    /// These are only warnings as plenty of mods work fine on versions they don't list
+    pub fn find_unsupported_mods(&self, version_overrides: &BTreeSet<PackageId>) -> Vec<PackageId> {
+        self.0
+            .iter()
+            .filter(|(package_id, mod_metadata)| {
+                !mod_metadata.supports_game_version && !version_overrides.contains(*package_id)
+            })
+            .map(|(package_id, _)| package_id.clone())
+            .collect()
+    }
+
+    /// Mods that have folders for other versions of the game but not this one
+    pub fn find_mods_without_version_content(&self) -> Vec<PackageId> {
+        self.0
+            .iter()
+            .filter(|(_, mod_metadata)| !mod_metadata.has_content_for_game_version)
+            .map(|(package_id, _)| package_id.clone())
+            .collect()
     }
 
     pub fn find_list_issues(&self, db: &ModRuleDb, issue_cache: &mut ModListIssueCache) {
         issue_cache.0.clear();
 
-        for (_, db) in db.0.iter() {
+        for (_, db) in db.enabled_dbs() {
             // Iter over the dbs
             // Iter over the rulesets in each db but only the ones in the list
             for (package_id, rule_entries, package_position) in
@@ -215,27 +681,840 @@ impl ModList {
             }
         }
 
+        // Anchored mods that aren't at their end of the list
+        let (start_anchors, end_anchors) = db.anchored_mods(self);
+
+        for (package_position, package_id) in self.0.keys().enumerate() {
+            if start_anchors.contains_key(package_id) {
+                for problem_package_id in self
+                    .0
+                    .keys()
+                    .take(package_position)
+                    .filter(|problem_package_id| !start_anchors.contains_key(*problem_package_id))
+                {
+                    issue_cache
+                        .0
+                        .entry(package_id.clone())
+                        .or_default()
+                        .insert(problem_package_id.clone(), ModRelation::Before);
+                }
+            }
+
+            if end_anchors.contains_key(package_id) {
+                for problem_package_id in self
+                    .0
+                    .keys()
+                    .skip(package_position + 1)
+                    .filter(|problem_package_id| !end_anchors.contains_key(*problem_package_id))
+                {
+                    issue_cache
+                        .0
+                        .entry(package_id.clone())
+                        .or_default()
+                        .insert(problem_package_id.clone(), ModRelation::After);
+                }
+            }
+        }
+
         // Remove empty entries
         issue_cache.0.retain(|_, issues| !issues.is_empty());
     }
 }
 
-#[derive(Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum ModdbType {
     ModBuiltRules,
+    /// The rule file bundled with rimmanager
+    DefaultRules,
     RuleFile(PathBuf),
 }
 
+impl fmt::Display for ModdbType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModdbType::ModBuiltRules => write!(f, "its About.xml"),
+            ModdbType::DefaultRules => write!(f, "the default rules"),
+            ModdbType::RuleFile(path) => write!(f, "the rule file {}", path.display()),
+        }
+    }
+}
+
+/// The rules shipped inside the binary
+const DEFAULT_RULES: &str = include_str!("../data/default-mod-rules.ruledb");
+
 #[derive(Default, Serialize, Deserialize)]
-pub struct ModRuleDb(pub IndexMap<ModdbType, HashMap<PackageId, ModRules>>);
+pub struct ModRuleDb {
+    pub dbs: IndexMap<ModdbType, HashMap<PackageId, ModRules>>,
+    /// Dbs the user turned off. They stay loaded so they can be turned back on
+    #[serde(default)]
+    pub disabled: HashSet<ModdbType>,
+}
+
+/// Tarjan's algorithm over an adjacency list of (successor, edge) pairs
+fn strongly_connected_components(successors: &[Vec<(usize, usize)>]) -> Vec<Vec<usize>> {
+    struct State<'a> {
+        successors: &'a [Vec<(usize, usize)>],
+        index: usize,
+        indices: Vec<Option<usize>>,
+        low_links: Vec<usize>,
+        stack: Vec<usize>,
+        on_stack: Vec<bool>,
+        components: Vec<Vec<usize>>,
+    }
+
+    fn visit(state: &mut State, node: usize) {
+        state.indices[node] = Some(state.index);
+        state.low_links[node] = state.index;
+        state.index += 1;
+        state.stack.push(node);
+        state.on_stack[node] = true;
+
+        for (successor, _) in state.successors[node].iter() {
+            match state.indices[*successor] {
+                None => {
+                    visit(state, *successor);
+                    state.low_links[node] = state.low_links[node].min(state.low_links[*successor]);
+                }
+                Some(successor_index) if state.on_stack[*successor] => {
+                    state.low_links[node] = state.low_links[node].min(successor_index);
+                }
+                _ => (),
+            }
+        }
+
+        if Some(state.low_links[node]) == state.indices[node] {
+            let mut component = Vec::new();
+
+            loop {
+                let member = state.stack.pop().unwrap();
+                state.on_stack[member] = false;
+                component.push(member);
+
+                if member == node {
+                    break;
+                }
+            }
+
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        successors,
+        index: 0,
+        indices: vec![None; successors.len()],
+        low_links: vec![0; successors.len()],
+        stack: Vec::new(),
+        on_stack: vec![false; successors.len()],
+        components: Vec::new(),
+    };
+
+    for node in 0..successors.len() {
+        if state.indices[node].is_none() {
+            visit(&mut state, node);
+        }
+    }
+
+    state.components
+}
 
 impl ModRuleDb {
+    /// The dbs that aren't turned off
+    pub fn enabled_dbs(&self) -> impl Iterator<Item = (&ModdbType, &HashMap<PackageId, ModRules>)> {
+        self.dbs
+            .iter()
+            .filter(|(db_type, _)| !self.disabled.contains(*db_type))
+    }
+
+    pub fn is_enabled(&self, db_type: &ModdbType) -> bool {
+        !self.disabled.contains(db_type)
+    }
+
+    pub fn set_enabled(&mut self, db_type: &ModdbType, enabled: bool) {
+        if enabled {
+            self.disabled.remove(db_type);
+        } else {
+            self.disabled.insert(db_type.clone());
+        }
+    }
+
+    /// Every rule in the enabled dbs that involves a mod, from its point of view
+    pub fn relations_of(&self, package_id: &PackageId) -> Vec<ModRelationEntry> {
+        let mut relations = Vec::new();
+
+        for (source, db) in self.enabled_dbs() {
+            for (declared_by, rules) in db {
+                let is_own_rule = declared_by == package_id;
+
+                let related = if is_own_rule {
+                    rules.rules.iter().collect()
+                } else {
+                    // Rules other mods declare about this one
+                    match rules.rules.get(package_id) {
+                        Some(relation) => vec![(declared_by, relation)],
+                        None => continue,
+                    }
+                };
+
+                for (other_package_id, relation) in related {
+                    if other_package_id == package_id {
+                        continue;
+                    }
+
+                    let kind = match (relation, is_own_rule) {
+                        (ModRelation::Dependency, true) => RelationKind::DependsOn,
+                        (ModRelation::Dependency, false) => RelationKind::NeededBy,
+                        (ModRelation::Before, true) | (ModRelation::After, false) => {
+                            RelationKind::LoadsBefore
+                        }
+                        (ModRelation::After, true) | (ModRelation::Before, false) => {
+                            RelationKind::LoadsAfter
+                        }
+                        (ModRelation::Incompatibility, _) => RelationKind::IncompatibleWith,
+                    };
+
+                    relations.push(ModRelationEntry {
+                        kind,
+                        other_package_id: other_package_id.clone(),
+                        declared_by: declared_by.clone(),
+                        source: source.clone(),
+                    });
+                }
+            }
+        }
+
+        relations.sort_by(|a, b| (a.kind, &a.other_package_id).cmp(&(b.kind, &b.other_package_id)));
+
+        relations
+    }
+
+    /// Every mod a mod depends on according to the enabled dbs
+    pub fn dependencies_of(&self, package_id: &PackageId) -> BTreeSet<&PackageId> {
+        self.enabled_dbs()
+            .filter_map(|(_, db)| db.get(package_id))
+            .flat_map(|rules| &rules.rules)
+            .filter(|(_, relation)| matches!(relation, ModRelation::Dependency))
+            .map(|(dependency, _)| dependency)
+            .collect()
+    }
+
+    /// Rule sets from every db for the mods in the list
+    pub fn rules_for_list<'a>(
+        &'a self,
+        list: &'a ModList,
+    ) -> impl Iterator<Item = (&'a PackageId, &'a ModRules)> {
+        self.enabled_dbs()
+            .flat_map(|(_, db)| db.iter())
+            .filter(|(package_id, _)| list.0.contains_key(*package_id))
+    }
+
+    /// All the load order requirements between mods that are both in the list
+    pub fn load_order_edges(&self, list: &ModList) -> Vec<LoadOrderEdge> {
+        let mut edges = Vec::new();
+
+        for (source, package_id, rules) in self.enabled_dbs().flat_map(|(source, db)| {
+            db.iter()
+                .filter(|(package_id, _)| list.0.contains_key(*package_id))
+                .map(move |(package_id, rules)| (source, package_id, rules))
+        }) {
+            for (other_package_id, relation) in &rules.rules {
+                if !list.0.contains_key(other_package_id) || package_id == other_package_id {
+                    continue;
+                }
+
+                let (before, after) = match relation {
+                    ModRelation::Before => (package_id, other_package_id),
+                    ModRelation::After | ModRelation::Dependency => (other_package_id, package_id),
+                    ModRelation::Incompatibility => continue,
+                };
+
+                edges.push(LoadOrderEdge {
+                    before: before.clone(),
+                    after: after.clone(),
+                    declared_by: package_id.clone(),
+                    rule: LoadOrderRule::Relation(relation.clone()),
+                    source: source.clone(),
+                });
+            }
+        }
+
+        // Anchored mods go above or below everything that isn't anchored the same way
+        let (start_anchors, end_anchors) = self.anchored_mods(list);
+
+        for (anchored_package_id, source) in &start_anchors {
+            for package_id in list
+                .0
+                .keys()
+                .filter(|package_id| !start_anchors.contains_key(*package_id))
+            {
+                edges.push(LoadOrderEdge {
+                    before: anchored_package_id.clone(),
+                    after: package_id.clone(),
+                    declared_by: anchored_package_id.clone(),
+                    rule: LoadOrderRule::StartAnchor,
+                    source: source.clone(),
+                });
+            }
+        }
+
+        for (anchored_package_id, source) in &end_anchors {
+            for package_id in list
+                .0
+                .keys()
+                .filter(|package_id| !end_anchors.contains_key(*package_id))
+            {
+                edges.push(LoadOrderEdge {
+                    before: package_id.clone(),
+                    after: anchored_package_id.clone(),
+                    declared_by: anchored_package_id.clone(),
+                    rule: LoadOrderRule::EndAnchor,
+                    source: source.clone(),
+                });
+            }
+        }
+
+        edges
+    }
+
+    /// The start and end anchored mods in the list along with the db that anchored them
+    pub fn anchored_mods(
+        &self,
+        list: &ModList,
+    ) -> (HashMap<PackageId, ModdbType>, HashMap<PackageId, ModdbType>) {
+        let mut start_anchors = HashMap::new();
+        let mut end_anchors = HashMap::new();
+
+        for (source, db) in self.enabled_dbs() {
+            for (package_id, rules) in db
+                .iter()
+                .filter(|(package_id, _)| list.0.contains_key(*package_id))
+            {
+                if rules.start_anchor {
+                    start_anchors
+                        .entry(package_id.clone())
+                        .or_insert_with(|| source.clone());
+                }
+
+                if rules.end_anchor {
+                    end_anchors
+                        .entry(package_id.clone())
+                        .or_insert_with(|| source.clone());
+                }
+            }
+        }
+
+        (start_anchors, end_anchors)
+    }
+
+    /// Finds the circular dependencies in the list
+    /// Every strongly connected component with more than one mod has at least one cycle and we report the shortest one in each
+    pub fn find_cycles(&self, list: &ModList) -> Vec<Vec<LoadOrderEdge>> {
+        let edges = self.load_order_edges(list);
+
+        let mut successors = vec![Vec::new(); list.0.len()];
+        for (edge_index, edge) in edges.iter().enumerate() {
+            let before = list.0.get_index_of(&edge.before).unwrap();
+            let after = list.0.get_index_of(&edge.after).unwrap();
+
+            successors[before].push((after, edge_index));
+        }
+
+        let mut cycles = Vec::new();
+
+        for component in strongly_connected_components(&successors) {
+            if component.len() < 2 {
+                continue;
+            }
+
+            let in_component: HashSet<_> = component.iter().copied().collect();
+            let start = *component.iter().min().unwrap();
+
+            // Breadth first search for the shortest way back to where we started
+            let mut came_from: HashMap<usize, usize> = HashMap::new();
+            let mut queue = VecDeque::from([start]);
+
+            'search: while let Some(node) = queue.pop_front() {
+                for (successor, edge_index) in &successors[node] {
+                    if !in_component.contains(successor) || came_from.contains_key(successor) {
+                        continue;
+                    }
+
+                    came_from.insert(*successor, *edge_index);
+
+                    if *successor == start {
+                        break 'search;
+                    }
+
+                    queue.push_back(*successor);
+                }
+            }
+
+            let mut cycle = Vec::new();
+            let mut node = start;
+
+            loop {
+                let edge = &edges[came_from[&node]];
+                cycle.push(edge.clone());
+                node = list.0.get_index_of(&edge.before).unwrap();
+
+                if node == start {
+                    break;
+                }
+            }
+
+            cycle.reverse();
+            cycles.push(cycle);
+        }
+
+        cycles
+    }
+
     pub fn add_db(&mut self, path: &Path) -> Result<(), anyhow::Error> {
         let db_text = String::from_utf8(fs::read(path)?)?;
         let db = toml::from_str(&db_text)?;
 
-        self.0.insert(ModdbType::RuleFile(path.to_owned()), db);
+        self.dbs.insert(ModdbType::RuleFile(path.to_owned()), db);
+
+        Ok(())
+    }
+
+    pub fn add_default_db(&mut self) -> Result<(), anyhow::Error> {
+        let db = toml::from_str(DEFAULT_RULES)?;
+
+        self.dbs.insert(ModdbType::DefaultRules, db);
 
         Ok(())
     }
+
+    /// Loads every .ruledb file in the folder, creating it if it doesn't exist so users can find it
+    /// Files that fail to load are skipped and returned alongside the reason
+    pub fn add_db_folder(
+        &mut self,
+        folder: &Path,
+    ) -> Result<Vec<(PathBuf, anyhow::Error)>, anyhow::Error> {
+        fs::create_dir_all(folder)?;
+
+        let mut db_paths = folder
+            .read_dir()?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.is_file()
+                    && path
+                        .extension()
+                        .is_some_and(|extension| extension == "ruledb")
+            })
+            .collect::<Vec<_>>();
+        // Keep the load order predictable
+        db_paths.sort();
+
+        let mut failures = Vec::new();
+
+        for db_path in db_paths {
+            log::info!("Loading rule file: {}", db_path.display());
+
+            if let Err(error) = self.add_db(&db_path) {
+                log::warn!("Can't load rule file {}: {}", db_path.display(), error);
+                failures.push((db_path, error));
+            }
+        }
+
+        Ok(failures)
+    }
+
+    /// Reads the db again from wherever it came from
+    /// The rules built from About.xml files can only be reloaded by rescanning
+    pub fn reload_db(&mut self, db_type: &ModdbType) -> Result<(), anyhow::Error> {
+        match db_type {
+            ModdbType::ModBuiltRules => Ok(()),
+            ModdbType::DefaultRules => self.add_default_db(),
+            ModdbType::RuleFile(path) => self.add_db(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn package_id(raw: &str) -> PackageId {
+        PackageId(raw.to_owned())
+    }
+
+    fn mod_list(package_ids: &[&str]) -> ModList {
+        ModList(
+            package_ids
+                .iter()
+                .map(|raw| {
+                    (
+                        package_id(raw),
+                        CondensedModMetadata {
+                            displayable_name: raw.to_string(),
+                            ..Default::default()
+                        },
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    fn rules(relations: &[(&str, ModRelation)]) -> ModRules {
+        ModRules {
+            rules: relations
+                .iter()
+                .map(|(other, relation)| (package_id(other), relation.clone()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn rule_db(dbs: Vec<(ModdbType, Vec<(&str, ModRules)>)>) -> ModRuleDb {
+        ModRuleDb {
+            dbs: dbs
+                .into_iter()
+                .map(|(db_type, db)| {
+                    (
+                        db_type,
+                        db.into_iter()
+                            .map(|(raw, rules)| (package_id(raw), rules))
+                            .collect(),
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn order(list: &ModList) -> Vec<&str> {
+        list.0
+            .keys()
+            .map(|package_id| package_id.0.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn sort_keeps_the_existing_order_as_the_tie_break() {
+        let db = rule_db(vec![(
+            ModdbType::ModBuiltRules,
+            vec![("x.b", rules(&[("x.a", ModRelation::Before)]))],
+        )]);
+        let mut list = mod_list(&["x.c", "x.a", "x.d", "x.b"]);
+
+        list.sort(&db, &mut ModList::default()).unwrap();
+
+        // x.a has to wait for x.b, everything else stays in place
+        assert_eq!(order(&list), ["x.c", "x.d", "x.b", "x.a"]);
+
+        let mut list = mod_list(&["x.d", "x.b", "x.c", "x.a"]);
+        list.sort(&db, &mut ModList::default()).unwrap();
+
+        assert_eq!(order(&list), ["x.d", "x.b", "x.c", "x.a"]);
+    }
+
+    #[test]
+    fn pulled_in_dependencies_go_right_above_the_mod_that_needs_them() {
+        let db = rule_db(vec![(
+            ModdbType::ModBuiltRules,
+            vec![
+                ("x.x", rules(&[("x.d", ModRelation::Dependency)])),
+                ("x.d", rules(&[("x.e", ModRelation::Dependency)])),
+            ],
+        )]);
+        let mut list = mod_list(&["x.core", "x.x", "x.y", "x.z", "x.w"]);
+        let mut inactive_list = mod_list(&["x.e", "x.d", "x.unrelated"]);
+
+        list.sort(&db, &mut inactive_list).unwrap();
+
+        assert_eq!(
+            order(&list),
+            ["x.core", "x.e", "x.d", "x.x", "x.y", "x.z", "x.w"]
+        );
+        assert_eq!(order(&inactive_list), ["x.unrelated"]);
+    }
+
+    #[test]
+    fn autofix_leaves_the_lists_alone_when_a_dependency_is_missing() {
+        let db = rule_db(vec![(
+            ModdbType::ModBuiltRules,
+            vec![(
+                "x.a",
+                rules(&[
+                    ("x.b", ModRelation::Dependency),
+                    ("x.z", ModRelation::Dependency),
+                    ("x.missing", ModRelation::Dependency),
+                ]),
+            )],
+        )]);
+        let mut list = mod_list(&["x.a"]);
+        let mut inactive_list = mod_list(&["x.b", "x.z"]);
+
+        let result = list.autofix(&db, &mut inactive_list, &mut ModListIssueCache::default());
+
+        assert_eq!(
+            result,
+            Err(SortError::MissingDependency {
+                package_id: package_id("x.a"),
+                dependency: package_id("x.missing"),
+            })
+        );
+        assert_eq!(order(&list), ["x.a"]);
+        assert_eq!(order(&inactive_list), ["x.b", "x.z"]);
+    }
+
+    #[test]
+    fn cycles_are_reported_with_where_each_rule_came_from() {
+        let rule_file = ModdbType::RuleFile(PathBuf::from("user.ruledb"));
+        let db = rule_db(vec![
+            (
+                ModdbType::ModBuiltRules,
+                vec![("x.b", rules(&[("x.a", ModRelation::After)]))],
+            ),
+            (
+                rule_file.clone(),
+                vec![("x.a", rules(&[("x.b", ModRelation::After)]))],
+            ),
+        ]);
+        let list = mod_list(&["x.a", "x.b", "x.c"]);
+
+        let cycles = db.find_cycles(&list);
+
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(format_cycle(&cycles[0]), "x.a → x.b → x.a");
+        assert_eq!(cycles[0][0].source, ModdbType::ModBuiltRules);
+        assert_eq!(cycles[0][0].declared_by, package_id("x.b"));
+        assert_eq!(cycles[0][1].source, rule_file);
+        assert_eq!(cycles[0][1].declared_by, package_id("x.a"));
+
+        let mut sorted_list = mod_list(&["x.a", "x.b", "x.c"]);
+        assert_eq!(
+            sorted_list.sort(&db, &mut ModList::default()),
+            Err(SortError::CircularDependency(cycles))
+        );
+    }
+
+    #[test]
+    fn anchored_mods_go_to_the_ends() {
+        let db = rule_db(vec![(
+            ModdbType::RuleFile(PathBuf::from("user.ruledb")),
+            vec![
+                (
+                    "x.start",
+                    ModRules {
+                        start_anchor: true,
+                        ..Default::default()
+                    },
+                ),
+                (
+                    "x.end",
+                    ModRules {
+                        end_anchor: true,
+                        ..Default::default()
+                    },
+                ),
+            ],
+        )]);
+        let mut list = mod_list(&["x.end", "x.a", "x.start", "x.b"]);
+
+        list.sort(&db, &mut ModList::default()).unwrap();
+
+        assert_eq!(order(&list), ["x.start", "x.a", "x.b", "x.end"]);
+    }
+
+    #[test]
+    fn inserted_mods_go_as_far_down_as_the_rules_allow() {
+        let db = rule_db(vec![(
+            ModdbType::DefaultRules,
+            vec![("x.new", rules(&[("x.b", ModRelation::Before)]))],
+        )]);
+        let mut list = mod_list(&["x.a", "x.b", "x.c"]);
+        let mut inserted = mod_list(&["x.new", "x.free"]);
+
+        for (package_id, mod_metadata) in inserted.0.drain(..) {
+            list.insert_in_place(&db, package_id, mod_metadata);
+        }
+
+        assert_eq!(order(&list), ["x.a", "x.new", "x.b", "x.c", "x.free"]);
+    }
+
+    #[test]
+    fn activation_plans_list_unsupported_dependencies_unless_allowed() {
+        let db = rule_db(vec![(
+            ModdbType::ModBuiltRules,
+            vec![
+                ("x.x", rules(&[("x.d", ModRelation::Dependency)])),
+                (
+                    "x.d",
+                    rules(&[
+                        ("x.e", ModRelation::Dependency),
+                        ("x.missing", ModRelation::Dependency),
+                    ]),
+                ),
+            ],
+        )]);
+        let active_list = mod_list(&["x.core"]);
+        let inactive_list = mod_list(&["x.x", "x.d", "x.e"]);
+
+        let plan = inactive_list.plan_activation(
+            &db,
+            &active_list,
+            &package_id("x.x"),
+            &BTreeSet::from([package_id("x.e")]),
+        );
+
+        assert_eq!(plan.dependencies, [package_id("x.e"), package_id("x.d")]);
+        assert_eq!(plan.missing, [(package_id("x.d"), package_id("x.missing"))]);
+        assert_eq!(plan.unsupported, [package_id("x.d")]);
+    }
+
+    #[test]
+    fn deactivation_plans_follow_dependents_of_dependents() {
+        let db = rule_db(vec![(
+            ModdbType::ModBuiltRules,
+            vec![
+                ("x.b", rules(&[("x.a", ModRelation::Dependency)])),
+                ("x.c", rules(&[("x.b", ModRelation::Dependency)])),
+                ("x.d", rules(&[("x.a", ModRelation::After)])),
+            ],
+        )]);
+        let list = mod_list(&["x.core", "x.c", "x.a", "x.d", "x.b"]);
+
+        let plan = list.plan_deactivation(&db, &package_id("x.a"));
+        assert_eq!(plan.dependents, [package_id("x.c"), package_id("x.b")]);
+
+        let mut with_dependents = list.clone();
+        let mut inactive_list = ModList::default();
+        with_dependents.deactivate(&mut inactive_list, &plan, true);
+
+        assert_eq!(order(&with_dependents), ["x.core", "x.d"]);
+        assert_eq!(order(&inactive_list), ["x.a", "x.c", "x.b"]);
+
+        let mut alone = list.clone();
+        let mut inactive_list = ModList::default();
+        alone.deactivate(&mut inactive_list, &plan, false);
+
+        assert_eq!(order(&alone), ["x.core", "x.c", "x.d", "x.b"]);
+        assert_eq!(order(&inactive_list), ["x.a"]);
+    }
+
+    #[test]
+    fn relations_show_up_on_both_mods() {
+        let db = rule_db(vec![
+            (
+                ModdbType::ModBuiltRules,
+                vec![(
+                    "x.a",
+                    rules(&[
+                        ("x.b", ModRelation::Before),
+                        ("x.c", ModRelation::Dependency),
+                    ]),
+                )],
+            ),
+            (
+                ModdbType::DefaultRules,
+                vec![("x.c", rules(&[("x.b", ModRelation::After)]))],
+            ),
+        ]);
+        let relations = |raw: &str| {
+            db.relations_of(&package_id(raw))
+                .into_iter()
+                .map(|relation| {
+                    (
+                        format!("{} {}", relation.kind, relation.other_package_id.0),
+                        relation.declared_by.0,
+                        relation.source,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            relations("x.a"),
+            [
+                (
+                    "Depends on x.c".to_owned(),
+                    "x.a".to_owned(),
+                    ModdbType::ModBuiltRules
+                ),
+                (
+                    "Loads before x.b".to_owned(),
+                    "x.a".to_owned(),
+                    ModdbType::ModBuiltRules
+                ),
+            ]
+        );
+        assert_eq!(
+            relations("x.b"),
+            [
+                (
+                    "Loads before x.c".to_owned(),
+                    "x.c".to_owned(),
+                    ModdbType::DefaultRules
+                ),
+                (
+                    "Loads after x.a".to_owned(),
+                    "x.a".to_owned(),
+                    ModdbType::ModBuiltRules
+                ),
+            ]
+        );
+        assert_eq!(
+            relations("x.c"),
+            [
+                (
+                    "Needed by x.a".to_owned(),
+                    "x.a".to_owned(),
+                    ModdbType::ModBuiltRules
+                ),
+                (
+                    "Loads after x.b".to_owned(),
+                    "x.c".to_owned(),
+                    ModdbType::DefaultRules
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_default_rules_load() {
+        let mut db = ModRuleDb::default();
+        db.add_default_db().unwrap();
+
+        assert!(db.dbs[&ModdbType::DefaultRules][&package_id("ludeon.rimworld")].start_anchor);
+    }
+
+    #[test]
+    fn rule_folders_load_every_rule_file_and_give_back_the_broken_ones() {
+        let rule_dir = crate::temporary_dir("rule-folder");
+        fs::write(
+            rule_dir.join("good.ruledb"),
+            "[\"x.a\"]\nend_anchor = true\n\n[\"x.a\".rules]\n\"x.b\" = \"After\"\n",
+        )
+        .unwrap();
+        fs::write(rule_dir.join("broken.ruledb"), "[\"x.a\"\n").unwrap();
+        fs::write(rule_dir.join("notes.txt"), "not a rule file").unwrap();
+
+        let mut db = ModRuleDb::default();
+        let failures = db.add_db_folder(&rule_dir).unwrap();
+
+        assert_eq!(
+            failures
+                .iter()
+                .map(|(path, _)| path.clone())
+                .collect::<Vec<_>>(),
+            [rule_dir.join("broken.ruledb")]
+        );
+        assert_eq!(db.dbs.len(), 1);
+
+        let rules = &db.dbs[&ModdbType::RuleFile(rule_dir.join("good.ruledb"))][&package_id("x.a")];
+        assert!(rules.end_anchor);
+        assert_eq!(rules.rules[&package_id("x.b")], ModRelation::After);
+    }
+
+    #[test]
+    fn missing_rule_folders_are_created() {
+        let rule_dir = crate::temporary_dir("missing-rule-folder").join("rules");
+
+        let mut db = ModRuleDb::default();
+        assert!(db.add_db_folder(&rule_dir).unwrap().is_empty());
+        assert!(rule_dir.is_dir());
+    }
 }
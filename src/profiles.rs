@@ -0,0 +1,267 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{managment::PackageId, resolve_config_dir};
+use anyhow::{anyhow, Error};
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// A named mod list kept separate from the game's ModsConfig.xml
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    /// In load order
+    #[serde(default)]
+    pub active_mods: Vec<PackageId>,
+    /// Mods allowed to be activated even though they don't declare support for the game version
+    #[serde(default)]
+    pub version_overrides: BTreeSet<PackageId>,
+}
+
+/// Every profile the user made
+/// Stored as profiles.toml in the config dir
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProfileStore {
+    /// The profile the active mod list belongs to, if any
+    pub current: Option<String>,
+    #[serde(default)]
+    pub profiles: IndexMap<String, Profile>,
+    /// Same as [Profile::version_overrides] but for when no profile is picked
+    #[serde(default)]
+    pub version_overrides: BTreeSet<PackageId>,
+}
+
+fn resolve_profiles_path() -> Option<PathBuf> {
+    Some(resolve_config_dir()?.join("profiles.toml"))
+}
+
+impl ProfileStore {
+    /// Reads the profile file, giving back an empty store if there isn't one yet
+    pub fn load() -> Result<Self, Error> {
+        let profiles_path =
+            resolve_profiles_path().ok_or_else(|| anyhow!("Can't find the config dir"))?;
+
+        if !profiles_path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Self::load_from(&profiles_path)
+    }
+
+    pub fn load_from(profiles_path: &Path) -> Result<Self, Error> {
+        let profiles_text = String::from_utf8(fs::read(profiles_path)?)?;
+        let mut store: Self = toml::from_str(&profiles_text)?;
+
+        // Don't point at a profile that isn't there
+        if let Some(current) = &store.current {
+            if !store.profiles.contains_key(current) {
+                store.current = None;
+            }
+        }
+
+        Ok(store)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let profiles_path =
+            resolve_profiles_path().ok_or_else(|| anyhow!("Can't find the config dir"))?;
+
+        self.save_to(&profiles_path)
+    }
+
+    pub fn save_to(&self, profiles_path: &Path) -> Result<(), Error> {
+        if let Some(parent) = profiles_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(profiles_path, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    pub fn current_profile(&self) -> Option<&Profile> {
+        self.profiles.get(self.current.as_ref()?)
+    }
+
+    pub fn current_profile_mut(&mut self) -> Option<&mut Profile> {
+        self.profiles.get_mut(self.current.as_ref()?)
+    }
+
+    pub fn current_version_overrides(&self) -> &BTreeSet<PackageId> {
+        match self.current_profile() {
+            Some(profile) => &profile.version_overrides,
+            None => &self.version_overrides,
+        }
+    }
+
+    pub fn current_version_overrides_mut(&mut self) -> &mut BTreeSet<PackageId> {
+        match self
+            .current
+            .as_ref()
+            .and_then(|name| self.profiles.get_mut(name))
+        {
+            Some(profile) => &mut profile.version_overrides,
+            None => &mut self.version_overrides,
+        }
+    }
+
+    fn check_new_name(&self, name: &str) -> Result<(), Error> {
+        if name.trim().is_empty() {
+            return Err(anyhow!("Profile names can't be empty"));
+        }
+
+        if self.profiles.contains_key(name) {
+            return Err(anyhow!("There is already a profile named {}", name));
+        }
+
+        Ok(())
+    }
+
+    pub fn create(&mut self, name: &str, profile: Profile) -> Result<(), Error> {
+        self.check_new_name(name)?;
+        self.profiles.insert(name.to_owned(), profile);
+
+        Ok(())
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), Error> {
+        self.check_new_name(new_name)?;
+
+        let index = self
+            .profiles
+            .get_index_of(name)
+            .ok_or_else(|| anyhow!("There is no profile named {}", name))?;
+        let (_, profile) = self.profiles.shift_remove_index(index).unwrap();
+        self.profiles
+            .shift_insert(index, new_name.to_owned(), profile);
+
+        if self.current.as_deref() == Some(name) {
+            self.current = Some(new_name.to_owned());
+        }
+
+        Ok(())
+    }
+
+    pub fn duplicate(&mut self, name: &str, new_name: &str) -> Result<(), Error> {
+        let profile = self
+            .profiles
+            .get(name)
+            .ok_or_else(|| anyhow!("There is no profile named {}", name))?
+            .clone();
+
+        self.create(new_name, profile)
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), Error> {
+        self.profiles
+            .shift_remove(name)
+            .ok_or_else(|| anyhow!("There is no profile named {}", name))?;
+
+        if self.current.as_deref() == Some(name) {
+            self.current = None;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporary_dir;
+
+    fn profile(active_mods: &[&str]) -> Profile {
+        Profile {
+            active_mods: active_mods
+                .iter()
+                .map(|package_id| PackageId(package_id.to_string()))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn names(store: &ProfileStore) -> Vec<&str> {
+        store.profiles.keys().map(String::as_str).collect()
+    }
+
+    /// Saves and loads the store again so every change is checked against what ends up on disk
+    fn round_trip(store: &ProfileStore, name: &str) -> ProfileStore {
+        let profiles_path = temporary_dir(name).join("profiles.toml");
+        store.save_to(&profiles_path).unwrap();
+
+        let loaded = ProfileStore::load_from(&profiles_path).unwrap();
+        assert_eq!(loaded, *store);
+        loaded
+    }
+
+    #[test]
+    fn profiles_are_created_duplicated_and_deleted() {
+        let mut store = ProfileStore::default();
+        store
+            .create("Vanilla", profile(&["ludeon.rimworld"]))
+            .unwrap();
+        store
+            .create("Modded", profile(&["ludeon.rimworld", "x.a"]))
+            .unwrap();
+        store.duplicate("Modded", "Modded copy").unwrap();
+        store.current = Some("Modded".to_owned());
+
+        let mut store = round_trip(&store, "profiles-create");
+        assert_eq!(names(&store), ["Vanilla", "Modded", "Modded copy"]);
+        assert_eq!(store.profiles["Modded copy"], store.profiles["Modded"]);
+
+        store.delete("Modded").unwrap();
+
+        let store = round_trip(&store, "profiles-delete");
+        assert_eq!(names(&store), ["Vanilla", "Modded copy"]);
+        assert_eq!(store.current, None);
+    }
+
+    #[test]
+    fn renaming_keeps_the_position_and_the_current_profile() {
+        let mut store = ProfileStore::default();
+        store.create("A", profile(&["x.a"])).unwrap();
+        store.create("B", profile(&["x.b"])).unwrap();
+        store.create("C", profile(&["x.c"])).unwrap();
+        store.current = Some("B".to_owned());
+
+        store.rename("B", "Renamed").unwrap();
+
+        let store = round_trip(&store, "profiles-rename");
+        assert_eq!(names(&store), ["A", "Renamed", "C"]);
+        assert_eq!(store.current.as_deref(), Some("Renamed"));
+        assert_eq!(store.profiles["Renamed"], profile(&["x.b"]));
+    }
+
+    #[test]
+    fn names_have_to_be_new_and_not_empty() {
+        let mut store = ProfileStore::default();
+        store.create("A", profile(&["x.a"])).unwrap();
+        store.create("B", profile(&["x.b"])).unwrap();
+
+        assert!(store.create("A", Profile::default()).is_err());
+        assert!(store.create("  ", Profile::default()).is_err());
+        assert!(store.rename("A", "B").is_err());
+        assert!(store.duplicate("A", "B").is_err());
+        assert!(store.rename("Missing", "D").is_err());
+        assert!(store.delete("Missing").is_err());
+
+        // Nothing changed by the failures
+        let store = round_trip(&store, "profiles-names");
+        assert_eq!(names(&store), ["A", "B"]);
+        assert_eq!(store.profiles["B"], profile(&["x.b"]));
+    }
+
+    #[test]
+    fn a_missing_current_profile_is_forgotten_on_load() {
+        let profiles_path = temporary_dir("profiles-stale-current").join("profiles.toml");
+        fs::write(&profiles_path, "current = \"Gone\"\n").unwrap();
+
+        assert_eq!(
+            ProfileStore::load_from(&profiles_path).unwrap().current,
+            None
+        );
+    }
+}
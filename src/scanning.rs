@@ -0,0 +1,593 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    thread::{self, JoinHandle},
+    time::SystemTime,
+};
+
+use crate::{
+    backups::write_file_atomically,
+    errors::FileError,
+    load_folders::ContentLayout,
+    managment::PackageId,
+    resolve_cache_dir,
+    version::GameVersion,
+    xml::{read_about_xml, recover_about_xml_identity, ModMetaData},
+};
+use anyhow::{anyhow, Error};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Bump this whenever [ModMetaData] changes shape so old caches get thrown away
+const CACHE_FORMAT_VERSION: u32 = 3;
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CachedAbout {
+    modified: SystemTime,
+    size: u64,
+    metadata: ModMetaData,
+}
+
+/// Parsed About.xml files keyed by their path
+/// An entry is only trusted while the file's modification time and size stay the same
+#[derive(Serialize, Deserialize)]
+struct AboutCache {
+    format_version: u32,
+    entries: HashMap<PathBuf, CachedAbout>,
+}
+
+impl Default for AboutCache {
+    fn default() -> Self {
+        Self {
+            format_version: CACHE_FORMAT_VERSION,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+fn resolve_about_cache_path() -> Option<PathBuf> {
+    Some(resolve_cache_dir()?.join("about-cache.json"))
+}
+
+impl AboutCache {
+    /// A missing, broken or outdated cache is treated as an empty one
+    fn load() -> Self {
+        let Some(cache_path) = resolve_about_cache_path().filter(|path| path.is_file()) else {
+            return Self::default();
+        };
+
+        let cache = fs::read(&cache_path)
+            .map_err(Error::from)
+            .and_then(|cache_data| Ok(serde_json::from_slice::<Self>(&cache_data)?));
+
+        match cache {
+            Ok(cache) if cache.format_version == CACHE_FORMAT_VERSION => cache,
+            Ok(_) => Self::default(),
+            Err(error) => {
+                log::info!("Not using the About.xml cache: {}", error);
+                Self::default()
+            }
+        }
+    }
+
+    fn save(&self) -> Result<(), Error> {
+        let cache_path =
+            resolve_about_cache_path().ok_or_else(|| anyhow!("Can't find the cache dir"))?;
+
+        if let Some(parent) = cache_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        write_file_atomically(&cache_path, &serde_json::to_vec(self)?)
+    }
+}
+
+/// How far along a scan is, updated from the worker threads
+#[derive(Default)]
+pub struct ScanProgress {
+    pub total: AtomicUsize,
+    pub scanned: AtomicUsize,
+}
+
+impl ScanProgress {
+    pub fn fraction(&self) -> f32 {
+        let total = self.total.load(Ordering::Relaxed);
+
+        if total == 0 {
+            return 0.0;
+        }
+
+        self.scanned.load(Ordering::Relaxed) as f32 / total as f32
+    }
+}
+
+/// A mod whose About.xml couldn't be used, with whatever could still be read out of it
+pub struct BrokenMod {
+    pub error: Error,
+    pub package_id: Option<PackageId>,
+    pub name: Option<String>,
+}
+
+impl BrokenMod {
+    pub fn new(mod_folder: &Path, error: Error) -> Self {
+        let (package_id, name) = recover_about_xml_identity(mod_folder);
+
+        Self {
+            error,
+            package_id,
+            name,
+        }
+    }
+}
+
+/// Files besides About.xml that are looked up on every scan as they change without About.xml changing
+#[derive(Default)]
+pub struct ModFiles {
+    /// Steam workshop id from About/PublishedFileId.txt
+    pub published_file_id: Option<String>,
+    pub preview_path: Option<PathBuf>,
+    pub icon_path: Option<PathBuf>,
+    /// LoadFolders.xml and the version folders
+    pub content_layout: ContentLayout,
+}
+
+/// Mod authors on Windows don't care about the case of file names, but other systems do
+fn find_file_ignoring_case(folder: &Path, file_name: &str) -> Option<PathBuf> {
+    let exact_path = folder.join(file_name);
+
+    if exact_path.is_file() {
+        return Some(exact_path);
+    }
+
+    folder
+        .read_dir()
+        .ok()?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| {
+            path.is_file()
+                && path
+                    .file_name()
+                    .is_some_and(|name| name.to_string_lossy().eq_ignore_ascii_case(file_name))
+        })
+}
+
+impl ModFiles {
+    pub fn find(mod_folder: &Path, about: &ModMetaData) -> Self {
+        let about_folder = mod_folder.join("About");
+
+        let published_file_id = fs::read_to_string(about_folder.join("PublishedFileId.txt"))
+            .ok()
+            .map(|published_file_id| published_file_id.trim().to_owned())
+            .filter(|published_file_id| !published_file_id.is_empty());
+
+        let icon_path = match &about.mod_icon_path {
+            Some(mod_icon_path) => [
+                mod_folder
+                    .join("Textures")
+                    .join(format!("{}.png", mod_icon_path)),
+                mod_folder.join(mod_icon_path),
+            ]
+            .into_iter()
+            .find(|path| path.is_file()),
+            None => find_file_ignoring_case(&about_folder, "ModIcon.png"),
+        };
+
+        Self {
+            published_file_id,
+            preview_path: find_file_ignoring_case(&about_folder, "Preview.png"),
+            icon_path,
+            content_layout: ContentLayout::find(mod_folder),
+        }
+    }
+}
+
+/// A mod folder and whatever we got out of its About.xml
+pub struct ScannedMod {
+    pub location: PathBuf,
+    pub about: Result<(ModMetaData, ModFiles), BrokenMod>,
+}
+
+pub struct ScanResult {
+    pub game_version: GameVersion,
+    /// Empty when only some mod folders were reread
+    pub scan_paths: Vec<PathBuf>,
+    /// In the order of the scan paths they were found in
+    pub mods: Vec<ScannedMod>,
+    /// Scan paths that couldn't be read, the rest of the scan goes on without them
+    pub errors: Vec<FileError>,
+}
+
+enum CacheLookup {
+    Hit,
+    Parsed {
+        modified: SystemTime,
+        size: u64,
+        about: Box<Result<ModMetaData, Error>>,
+    },
+    Unreadable(Error),
+}
+
+fn look_up_mod_folder(cache: &AboutCache, about_path: &Path, mod_folder: &Path) -> CacheLookup {
+    let file_metadata = match fs::metadata(about_path) {
+        Ok(file_metadata) => file_metadata,
+        Err(error) => {
+            return CacheLookup::Unreadable(
+                Error::from(error).context("Can't read About/About.xml"),
+            )
+        }
+    };
+    let modified = match file_metadata.modified() {
+        Ok(modified) => modified,
+        Err(error) => return CacheLookup::Unreadable(error.into()),
+    };
+    let size = file_metadata.len();
+
+    if let Some(cached) = cache.entries.get(about_path) {
+        if cached.modified == modified && cached.size == size {
+            return CacheLookup::Hit;
+        }
+    }
+
+    CacheLookup::Parsed {
+        modified,
+        size,
+        about: Box::new(read_about_xml(mod_folder)),
+    }
+}
+
+pub fn read_game_version(game_path: &Path) -> Result<GameVersion, FileError> {
+    let version_file_path = game_path.join("Version.txt");
+
+    let read = || -> Result<GameVersion, Error> {
+        String::from_utf8(fs::read(&version_file_path)?)?.parse()
+    };
+
+    read().map_err(|cause| FileError::ReadGameVersion {
+        path: version_file_path.clone(),
+        cause,
+    })
+}
+
+/// Reads every mod folder in the scan paths, parsing the About.xml files on all cores
+/// About.xml files that didn't change since the last scan come out of the cache instead
+pub fn scan_installation(
+    game_path: &Path,
+    scan_paths: Vec<PathBuf>,
+    progress: &ScanProgress,
+) -> Result<ScanResult, FileError> {
+    let game_version = read_game_version(game_path)?;
+
+    let mut mod_folders = Vec::new();
+    let mut errors = Vec::new();
+
+    // Find the folders of the mods
+    for scan_dir in &scan_paths {
+        let read_dir = match scan_dir.read_dir() {
+            Ok(read_dir) => read_dir,
+            Err(error) => {
+                errors.push(FileError::ScanFolder {
+                    path: scan_dir.clone(),
+                    cause: error.into(),
+                });
+                continue;
+            }
+        };
+
+        // Get all the folders we can read
+        // TODO: Warn about folders we can't read? Can't imagine this being too much of a issue through
+        for mod_folder in read_dir.flatten() {
+            let mod_folder = mod_folder.path();
+
+            // Only interact with directories
+            if mod_folder.is_dir() {
+                mod_folders.push(mod_folder);
+            }
+        }
+    }
+
+    // Symlinks and scan paths listed twice lead to the same mod more than once
+    let mut seen_folders = HashSet::new();
+    mod_folders.retain(|mod_folder| {
+        seen_folders.insert(
+            mod_folder
+                .canonicalize()
+                .unwrap_or_else(|_| mod_folder.clone()),
+        )
+    });
+
+    let mods = read_mod_folders(mod_folders, progress);
+
+    Ok(ScanResult {
+        game_version,
+        scan_paths,
+        mods,
+        errors,
+    })
+}
+
+/// Rereads only these mod folders, like after the watcher saw them change
+/// Folders that are gone come back as unreadable
+pub fn scan_mod_folders(
+    game_path: &Path,
+    mod_folders: Vec<PathBuf>,
+    progress: &ScanProgress,
+) -> Result<ScanResult, FileError> {
+    Ok(ScanResult {
+        game_version: read_game_version(game_path)?,
+        scan_paths: Vec::new(),
+        mods: read_mod_folders(mod_folders, progress),
+        errors: Vec::new(),
+    })
+}
+
+/// Parses the About.xml of every folder on all cores, going through the cache
+fn read_mod_folders(mod_folders: Vec<PathBuf>, progress: &ScanProgress) -> Vec<ScannedMod> {
+    let cache = AboutCache::load();
+    let (mods, new_cache) = read_mod_folders_through_cache(&cache, mod_folders, progress);
+
+    if let Some(new_cache) = new_cache {
+        if let Err(error) = new_cache.save() {
+            log::warn!("Can't save the About.xml cache: {}", error);
+        }
+    }
+
+    mods
+}
+
+/// Gives back the cache to save along with the mods if anything in it changed
+fn read_mod_folders_through_cache(
+    cache: &AboutCache,
+    mod_folders: Vec<PathBuf>,
+    progress: &ScanProgress,
+) -> (Vec<ScannedMod>, Option<AboutCache>) {
+    progress.total.store(mod_folders.len(), Ordering::Relaxed);
+
+    let lookups: Vec<_> = mod_folders
+        .into_par_iter()
+        .map(|mod_folder| {
+            let about_path = mod_folder.join("About").join("About.xml");
+            let lookup = look_up_mod_folder(cache, &about_path, &mod_folder);
+
+            // Looking for the other files walks the mod folder, so it has to happen here too
+            let metadata = match &lookup {
+                CacheLookup::Hit => Some(&cache.entries[&about_path].metadata),
+                CacheLookup::Parsed { about, .. } => about.as_ref().as_ref().ok(),
+                CacheLookup::Unreadable(_) => None,
+            };
+            let files = metadata.map(|metadata| ModFiles::find(&mod_folder, metadata));
+            progress.scanned.fetch_add(1, Ordering::Relaxed);
+
+            (mod_folder, about_path, lookup, files)
+        })
+        .collect();
+
+    let mut new_cache = AboutCache::default();
+    let mut is_cache_changed = false;
+    let mut mods = Vec::new();
+
+    for (mod_folder, about_path, lookup, files) in lookups {
+        let about = match lookup {
+            CacheLookup::Hit => {
+                let cached = cache.entries[&about_path].clone();
+                let metadata = cached.metadata.clone();
+                new_cache.entries.insert(about_path, cached);
+
+                Ok(metadata)
+            }
+            CacheLookup::Parsed {
+                modified,
+                size,
+                about,
+            } => {
+                let about = *about;
+
+                if let Ok(metadata) = &about {
+                    new_cache.entries.insert(
+                        about_path,
+                        CachedAbout {
+                            modified,
+                            size,
+                            metadata: metadata.clone(),
+                        },
+                    );
+                    is_cache_changed = true;
+                }
+
+                about
+            }
+            CacheLookup::Unreadable(error) => Err(error),
+        }
+        // The files were found for every About.xml that could be read
+        .map(|metadata| (metadata, files.unwrap_or_default()))
+        .map_err(|error| BrokenMod::new(&mod_folder, error));
+
+        mods.push(ScannedMod {
+            location: mod_folder,
+            about,
+        });
+    }
+
+    // Keep entries from other installations, but not ones whose mod was deleted
+    for (about_path, cached) in &cache.entries {
+        if new_cache.entries.contains_key(about_path) {
+            continue;
+        }
+
+        if about_path.is_file() {
+            new_cache.entries.insert(about_path.clone(), cached.clone());
+        } else {
+            is_cache_changed = true;
+        }
+    }
+
+    (mods, is_cache_changed.then_some(new_cache))
+}
+
+/// A scan running on its own thread so the interface keeps drawing
+pub struct BackgroundScan {
+    pub progress: Arc<ScanProgress>,
+    handle: JoinHandle<Result<ScanResult, FileError>>,
+}
+
+impl BackgroundScan {
+    pub fn start(game_path: PathBuf, scan_paths: Vec<PathBuf>) -> Self {
+        Self::spawn(move |progress| scan_installation(&game_path, scan_paths, progress))
+    }
+
+    pub fn start_mod_folders(game_path: PathBuf, mod_folders: Vec<PathBuf>) -> Self {
+        Self::spawn(move |progress| scan_mod_folders(&game_path, mod_folders, progress))
+    }
+
+    fn spawn(
+        scan: impl FnOnce(&ScanProgress) -> Result<ScanResult, FileError> + Send + 'static,
+    ) -> Self {
+        let progress = Arc::new(ScanProgress::default());
+        let thread_progress = progress.clone();
+
+        let handle = thread::spawn(move || scan(&thread_progress));
+
+        Self { progress, handle }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    /// Waits for the scan to end
+    pub fn join(self) -> Result<ScanResult, FileError> {
+        self.handle.join().map_err(|_| FileError::ScanCrashed)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{temporary_dir, xml::deserialize_from_xml_with_position};
+
+    fn about_xml(mod_icon_path: Option<&str>) -> ModMetaData {
+        deserialize_from_xml_with_position(&format!(
+            "<ModMetaData><packageId>x.a</packageId>{}</ModMetaData>",
+            mod_icon_path
+                .map(|mod_icon_path| format!("<modIconPath>{}</modIconPath>", mod_icon_path))
+                .unwrap_or_default()
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn mod_files_are_found_whatever_their_case() {
+        let mod_folder = temporary_dir("scanning-mod-files");
+        let about_folder = mod_folder.join("About");
+        fs::create_dir_all(&about_folder).unwrap();
+        fs::create_dir_all(mod_folder.join("1.5")).unwrap();
+        fs::write(about_folder.join("PublishedFileId.txt"), " 123456\n").unwrap();
+        fs::write(about_folder.join("preview.PNG"), "").unwrap();
+        fs::write(about_folder.join("modicon.png"), "").unwrap();
+
+        let mod_files = ModFiles::find(&mod_folder, &about_xml(None));
+
+        assert_eq!(mod_files.published_file_id.as_deref(), Some("123456"));
+        assert_eq!(
+            mod_files.preview_path,
+            Some(about_folder.join("preview.PNG"))
+        );
+        assert_eq!(mod_files.icon_path, Some(about_folder.join("modicon.png")));
+        assert_eq!(
+            mod_files
+                .content_layout
+                .version_folders
+                .values()
+                .collect::<Vec<_>>(),
+            ["1.5"]
+        );
+
+        // A modIconPath is a texture path, not a file in the About folder
+        fs::create_dir_all(mod_folder.join("Textures").join("UI")).unwrap();
+        fs::write(mod_folder.join("Textures").join("UI").join("Icon.png"), "").unwrap();
+
+        let mod_files = ModFiles::find(&mod_folder, &about_xml(Some("UI/Icon")));
+        fs::remove_dir_all(&mod_folder).unwrap();
+
+        assert_eq!(
+            mod_files.icon_path,
+            Some(mod_folder.join("Textures").join("UI/Icon.png"))
+        );
+    }
+
+    fn scanned_name(mods: &[ScannedMod]) -> Option<String> {
+        let (metadata, _) = mods[0].about.as_ref().ok()?;
+        metadata.name.clone()
+    }
+
+    /// Swaps what the cache remembers so it shows whether the file was parsed again
+    fn tampered(cache: Option<AboutCache>) -> AboutCache {
+        let mut cache = cache.unwrap();
+
+        for cached in cache.entries.values_mut() {
+            cached.metadata.name = Some("From the cache".to_owned());
+        }
+
+        cache
+    }
+
+    #[test]
+    fn cached_about_xml_is_used_until_the_file_changes() {
+        let mod_folder = temporary_dir("scanning-cache");
+        let about_path = mod_folder.join("About").join("About.xml");
+        fs::create_dir_all(about_path.parent().unwrap()).unwrap();
+        let write_about_xml = |name: &str| {
+            fs::write(
+                &about_path,
+                format!(
+                    "<ModMetaData><name>{}</name><packageId>x.a</packageId></ModMetaData>",
+                    name
+                ),
+            )
+            .unwrap();
+        };
+        let read = |cache: &AboutCache| {
+            read_mod_folders_through_cache(
+                cache,
+                vec![mod_folder.clone()],
+                &ScanProgress::default(),
+            )
+        };
+
+        write_about_xml("First");
+        let (mods, new_cache) = read(&AboutCache::default());
+        assert_eq!(scanned_name(&mods).as_deref(), Some("First"));
+
+        // Same modification time and size
+        let cache = tampered(new_cache);
+        let (mods, new_cache) = read(&cache);
+        assert_eq!(scanned_name(&mods).as_deref(), Some("From the cache"));
+        assert!(new_cache.is_none());
+
+        // Different size
+        write_about_xml("Second one");
+        let (mods, new_cache) = read(&cache);
+        assert_eq!(scanned_name(&mods).as_deref(), Some("Second one"));
+
+        // Same size but a different modification time
+        let cache = tampered(new_cache);
+        let modified = cache.entries[&about_path].modified;
+        write_about_xml("Second two");
+        fs::File::options()
+            .write(true)
+            .open(&about_path)
+            .unwrap()
+            .set_modified(modified + std::time::Duration::from_secs(60))
+            .unwrap();
+        let (mods, new_cache) = read(&cache);
+        assert_eq!(scanned_name(&mods).as_deref(), Some("Second two"));
+        assert!(new_cache.is_some());
+
+        fs::remove_dir_all(&mod_folder).unwrap();
+    }
+}
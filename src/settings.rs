@@ -0,0 +1,181 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    does_directory_represent_valid_game_installation,
+    errors::FileError,
+    managment::{ModdbType, PackageId},
+    resolve_config_dir,
+    ui::RimManager,
+};
+use anyhow::{anyhow, Error};
+use indexmap::IndexSet;
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+
+/// Everything we remember between sessions
+/// Stored as settings.toml in the config dir
+#[derive(Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Settings {
+    pub game_path: Option<PathBuf>,
+    pub steam_path: Option<PathBuf>,
+    /// In the order they are scanned, which decides the default copy of a mod installed more than once
+    #[serde(default)]
+    pub mod_folder_paths: IndexSet<PathBuf>,
+    pub last_selected_mod: Option<PackageId>,
+    /// Rule files that were loaded, including ones outside of the rule folder
+    #[serde(default)]
+    pub rule_files: BTreeSet<PathBuf>,
+    /// Rule databases the user turned off
+    #[serde(default)]
+    pub disabled_rule_dbs: Vec<ModdbType>,
+    /// Which copy to use for mods installed more than once
+    #[serde(default)]
+    pub preferred_mod_locations: BTreeMap<PackageId, PathBuf>,
+}
+
+fn resolve_settings_path() -> Option<PathBuf> {
+    Some(resolve_config_dir()?.join("settings.toml"))
+}
+
+impl Settings {
+    /// Reads the settings file, giving back the defaults if there isn't one yet
+    pub fn load() -> Result<Self, Error> {
+        let settings_path =
+            resolve_settings_path().ok_or_else(|| anyhow!("Can't find the config dir"))?;
+
+        if !settings_path.is_file() {
+            return Ok(Self::default());
+        }
+
+        Self::load_from(&settings_path)
+    }
+
+    pub fn load_from(settings_path: &Path) -> Result<Self, Error> {
+        let settings_text = String::from_utf8(fs::read(settings_path)?)?;
+
+        Ok(toml::from_str(&settings_text)?)
+    }
+
+    pub fn save(&self) -> Result<(), Error> {
+        let settings_path =
+            resolve_settings_path().ok_or_else(|| anyhow!("Can't find the config dir"))?;
+
+        if let Some(parent) = settings_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(settings_path, toml::to_string_pretty(self)?)?;
+
+        Ok(())
+    }
+
+    /// Takes the parts of the manager worth remembering
+    pub fn capture(manager: &RimManager) -> Self {
+        Self {
+            game_path: manager.game_path.clone(),
+            steam_path: manager.steam_path.clone(),
+            mod_folder_paths: manager.mod_folder_paths.iter().cloned().collect(),
+            last_selected_mod: manager.currently_selected_mod.clone(),
+            rule_files: manager
+                .mod_rules
+                .dbs
+                .keys()
+                .filter_map(|db_type| match db_type {
+                    ModdbType::RuleFile(path) => Some(path.clone()),
+                    _ => None,
+                })
+                .collect(),
+            // Sorted so the file only changes when the set does
+            disabled_rule_dbs: manager
+                .mod_rules
+                .disabled
+                .iter()
+                .cloned()
+                .sorted()
+                .collect(),
+            preferred_mod_locations: manager.preferred_mod_locations.clone(),
+        }
+    }
+
+    /// Puts the settings back into the manager and starts scanning the installation if we know where it is
+    /// Expects the rule dbs and profiles to already be loaded
+    /// Gives back the rule files that couldn't be loaded
+    pub fn apply(&self, manager: &mut RimManager) -> Vec<FileError> {
+        let rule_errors = self.apply_without_scanning(manager);
+
+        if manager.game_path.is_some() {
+            // The scan drops the selection if the mod isn't around anymore
+            manager.currently_selected_mod = self.last_selected_mod.clone();
+            manager.rescan();
+        }
+
+        rule_errors
+    }
+
+    /// Puts the settings back into the manager, leaving the scan to the caller
+    /// Gives back the rule files that couldn't be loaded
+    pub fn apply_without_scanning(&self, manager: &mut RimManager) -> Vec<FileError> {
+        let mut rule_errors = Vec::new();
+
+        manager.game_path = self
+            .game_path
+            .clone()
+            .filter(|path| does_directory_represent_valid_game_installation(path));
+        manager.steam_path = self.steam_path.clone();
+        manager.mod_folder_paths = self.mod_folder_paths.iter().cloned().collect();
+
+        for rule_file in &self.rule_files {
+            if manager
+                .mod_rules
+                .dbs
+                .contains_key(&ModdbType::RuleFile(rule_file.clone()))
+            {
+                continue;
+            }
+
+            if let Err(cause) = manager.mod_rules.add_db(rule_file) {
+                log::warn!("Can't load rule file {}: {}", rule_file.display(), cause);
+                rule_errors.push(FileError::ReadRuleFile {
+                    path: rule_file.clone(),
+                    cause,
+                });
+            }
+        }
+
+        manager.mod_rules.disabled = self.disabled_rule_dbs.iter().cloned().collect();
+        manager.preferred_mod_locations = self.preferred_mod_locations.clone();
+
+        rule_errors
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn disabled_rule_dbs_are_saved_in_a_stable_order() {
+        let mut manager = RimManager::default();
+        manager.mod_rules.disabled = HashSet::from([
+            ModdbType::RuleFile(PathBuf::from("b.ruledb")),
+            ModdbType::DefaultRules,
+            ModdbType::RuleFile(PathBuf::from("a.ruledb")),
+            ModdbType::ModBuiltRules,
+        ]);
+
+        assert_eq!(
+            Settings::capture(&manager).disabled_rule_dbs,
+            [
+                ModdbType::ModBuiltRules,
+                ModdbType::DefaultRules,
+                ModdbType::RuleFile(PathBuf::from("a.ruledb")),
+                ModdbType::RuleFile(PathBuf::from("b.ruledb")),
+            ]
+        );
+    }
+}
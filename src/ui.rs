@@ -1,27 +1,79 @@
-use std::{collections::HashSet, fs, path::PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    path::{Path, PathBuf},
+    sync::atomic::Ordering,
+    time::Duration,
+};
 
 use crate::{
+    backups::{format_timestamp, list_backups, restore_backup, Backup, MAX_BACKUPS},
+    discovery::{discover_installations, find_workshop_paths, DiscoveredInstallation},
     does_directory_represent_valid_game_installation, does_directory_represent_valid_steam_prefix,
-    managment::{CondensedModMetadata, ModList, ModListIssueCache, ModRuleDb, PackageId},
-    parse_game_version,
-    xml::{read_about_xml, read_modconfig_xml, write_modconfig_xml},
+    errors::FileError,
+    managment::{
+        ActivationPlan, CondensedModMetadata, DeactivationPlan, ModList, ModListIssueCache,
+        ModRuleDb, ModSource, ModdbType, PackageId, RelationKind, SortError,
+    },
+    profiles::{Profile, ProfileStore},
+    resolve_rule_dir,
+    scanning::{
+        scan_installation, BackgroundScan, BrokenMod, ModFiles, ScanProgress, ScanResult,
+        ScannedMod,
+    },
+    settings::Settings,
+    version::GameVersion,
+    watcher::ModWatcher,
+    xml::{
+        read_modconfig_xml, read_modconfig_xml_from, read_modconfig_xml_or_default,
+        resolve_modconfig_xml_path, write_modconfig_xml, ModMetaData, ModsConfigData,
+    },
 };
-use anyhow::Error;
+use anyhow::{anyhow, Error};
 use egui::{Button, Image};
 use egui_dnd::dnd;
 use egui_file::FileDialog;
 use egui_modal::Modal;
 use homedir::get_my_home;
+use indexmap::{IndexMap, IndexSet};
+use itertools::Itertools;
 
 // TODO: Reorganize this and remove the code duplication
 // FIXME: A lot of redundant data being held here!!
 // TODO: Extract enough data that we don't carry about the About.xml for every mod. We are trying to save every cpu cycle and byte here
 // TODO: We might add loading screens and stuff althrough its not exactly needed considering how fast our code is
 
+/// What to do with the mods that depend on a mod being deactivated
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum DeactivationChoice {
+    /// Deactivate them too
+    WithDependents,
+    /// Leave them active with issues
+    Alone,
+}
+
+/// What to do with the mod lists once a background scan finishes
+pub enum AfterScan {
+    /// Put the current profile's mods into the active list
+    ApplyProfile,
+    /// Put the mods from this ModsConfig.xml into the active list
+    LoadModOrdering {
+        modconfig_xml_path: PathBuf,
+        mod_ordering: Vec<PackageId>,
+    },
+    /// Only some mod folders were reread, update those mods in place
+    UpdateModFolders,
+    /// Put these mods back into the active list, for rescans that shouldn't change it
+    KeepModOrdering(Vec<PackageId>),
+}
+
 #[derive(Default)]
 pub struct RimManager {
     /// Path to the game installation
     pub game_path: Option<PathBuf>,
+    /// Version of the game installation, known after the first scan
+    pub installed_game_version: Option<GameVersion>,
+    /// Version the mods are checked against instead of the installed one, for planning a list for another version
+    pub game_version_override: Option<GameVersion>,
     /// Path to the game installation
     pub steam_path: Option<PathBuf>,
     /// File picker dialog to get to the installation
@@ -29,7 +81,7 @@ pub struct RimManager {
     /// File picker dialog to get to the installation
     pub steam_path_picker_dialog: Option<FileDialog>,
     /// Paths including locations for mods
-    pub mod_folder_paths: HashSet<PathBuf>,
+    pub mod_folder_paths: IndexSet<PathBuf>,
     /// Mod being displayed in the sidebar
     pub currently_selected_mod: Option<PackageId>,
     /// List of mods that can be written or read into
@@ -39,230 +91,1787 @@ pub struct RimManager {
     pub inactive_search: String,
     /// Search bar for active mods
     pub active_search: String,
+    /// Sources whose mods are hidden from the inactive list
+    pub inactive_hidden_sources: BTreeSet<ModSource>,
+    /// Sources whose mods are hidden from the active list
+    pub active_hidden_sources: BTreeSet<ModSource>,
     /// Rule stuff
     pub mod_rules: ModRuleDb,
     pub mod_list_issue_cache: ModListIssueCache,
+    /// Why the last attempt at fixing the mod list failed
+    pub sort_error: Option<SortError>,
+    /// If the window for managing the rule databases is open
+    pub show_rule_db_window: bool,
+    /// Why the last rule database reload failed
+    pub rule_db_error: Option<Error>,
+    /// What was last written to the settings file so we only write when something changed
+    pub saved_settings: Settings,
+    /// Installations found in the usual places, offered so the user doesn't have to go looking
+    pub discovered_installations: Vec<DiscoveredInstallation>,
+    /// Named mod lists
+    pub profiles: ProfileStore,
+    /// What was last written to the profile file so we only write when something changed
+    pub saved_profiles: ProfileStore,
+    /// Text box for naming profiles
+    pub profile_name_input: String,
+    /// Why the last profile action failed
+    pub profile_error: Option<Error>,
+    /// Mods in the current profile that aren't installed
+    pub profile_missing_mods: Vec<PackageId>,
+    /// If the window for restoring backups is open
+    pub show_backup_window: bool,
+    /// Backups of ModsConfig.xml, refreshed when the window opens
+    pub backups: Vec<Backup>,
+    /// What happened to the last restore
+    pub backup_status: Option<String>,
+    /// Watches the mod folders and ModsConfig.xml, set up on every scan
+    pub mod_watcher: Option<ModWatcher>,
+    /// Active mods whose folder disappeared since the last scan
+    pub missing_mod_folders: HashSet<PackageId>,
+    /// If ModsConfig.xml was changed by something else since we last read it
+    pub modconfig_xml_changed: bool,
+    /// The scan running in the background, if any
+    pub scan_task: Option<(BackgroundScan, AfterScan)>,
+    /// Mod folders the watcher saw change that are waiting for the current scan to finish
+    pub pending_mod_folders: Vec<PathBuf>,
+    /// Failures shown until the user dismisses them
+    pub notifications: Vec<FileError>,
+    /// Mods whose About.xml couldn't be used, by folder
+    pub broken_mods: IndexMap<PathBuf, BrokenMod>,
+    /// If the window listing the broken mods is open
+    pub show_broken_mods_window: bool,
+    /// Mods installed more than once, with the folder of every copy in scan order
+    pub duplicate_mods: IndexMap<PackageId, Vec<PathBuf>>,
+    /// Which copy to use for mods installed more than once, the first one found otherwise
+    pub preferred_mod_locations: BTreeMap<PackageId, PathBuf>,
+    /// If the window for picking between copies of a mod is open
+    pub show_duplicate_mods_window: bool,
+    /// Activation waiting for the user to confirm the dependencies it brings along
+    pub pending_activation: Option<ActivationPlan>,
+    /// The checkbox for allowing the unsupported dependencies of the pending activation
+    pub allow_unsupported_dependencies: bool,
+    /// Deactivation waiting for the user to decide what happens to the mods depending on it
+    pub pending_deactivation: Option<DeactivationPlan>,
+    /// What to do with dependents from now on, if the user asked us to remember it
+    /// Deliberately not saved so a new session asks again
+    pub deactivation_choice: Option<DeactivationChoice>,
+    /// The checkbox for remembering the choice above
+    pub remember_deactivation_choice: bool,
 }
 
 impl RimManager {
-    pub fn refresh_metadata(&mut self) -> Result<(), Error> {
+    /// Loads the bundled rules and every rule file in the user's rule folder
+    /// Gives back the rule files that couldn't be loaded
+    pub fn load_rule_dbs(&mut self) -> Vec<FileError> {
+        if let Err(error) = self.mod_rules.add_default_db() {
+            log::error!("Can't load the default rules: {}", error);
+        }
+
+        match resolve_rule_dir() {
+            Some(rule_dir) => self.load_rule_folder(&rule_dir),
+            None => Vec::new(),
+        }
+    }
+
+    /// Loads every rule file in the folder that isn't loaded yet
+    /// Gives back the rule files that couldn't be loaded
+    pub fn load_rule_folder(&mut self, rule_dir: &Path) -> Vec<FileError> {
+        match self.mod_rules.add_db_folder(rule_dir) {
+            Ok(failures) => failures
+                .into_iter()
+                .map(|(path, cause)| FileError::ReadRuleFile { path, cause })
+                .collect(),
+            Err(cause) => vec![FileError::ReadRuleFolder {
+                path: rule_dir.to_owned(),
+                cause,
+            }],
+        }
+    }
+
+    /// Scans the installation without leaving this thread, for when there is no interface to keep drawing
+    /// Gives back the folders that couldn't be scanned
+    pub fn refresh_metadata(&mut self) -> Result<Vec<FileError>, Error> {
+        let game_path = self
+            .game_path
+            .clone()
+            .ok_or_else(|| anyhow!("No game path has been set"))?;
+        let scan_result =
+            scan_installation(&game_path, self.scan_paths(), &ScanProgress::default())?;
+
+        Ok(self.apply_scan_result(scan_result))
+    }
+
+    /// Replaces the mod lists with what a scan found
+    /// Everything ends up in the inactive list
+    /// Gives back the folders that couldn't be scanned
+    pub fn apply_scan_result(&mut self, scan_result: ScanResult) -> Vec<FileError> {
         self.active_mod_list.0.clear();
         self.inactive_mod_list.0.clear();
         self.mod_list_issue_cache.0.clear();
+        self.missing_mod_folders.clear();
+        self.broken_mods.clear();
+        self.duplicate_mods.clear();
+        // These get rebuilt from the About.xml files below
+        self.mod_rules
+            .dbs
+            .entry(ModdbType::ModBuiltRules)
+            .or_default()
+            .clear();
+
+        self.installed_game_version = Some(scan_result.game_version);
+        let game_version = self
+            .game_version_override
+            .unwrap_or(scan_result.game_version);
+
+        // The same mod can be in more than one scan path, so gather the copies before picking one
+        let mut mod_copies: IndexMap<PackageId, Vec<(PathBuf, ModMetaData, ModFiles)>> =
+            IndexMap::new();
+
+        for scanned_mod in scan_result.mods {
+            match scanned_mod.about {
+                Ok((about_file_xml, mod_files)) => {
+                    mod_copies
+                        .entry(about_file_xml.package_id.clone())
+                        .or_default()
+                        .push((scanned_mod.location, about_file_xml, mod_files));
+                }
+                Err(broken_mod) => {
+                    self.broken_mods.insert(scanned_mod.location, broken_mod);
+                }
+            }
+        }
+
+        for (package_id, mut copies) in mod_copies {
+            if copies.len() > 1 {
+                log::info!("{} is installed more than once", package_id.0);
+                self.duplicate_mods.insert(
+                    package_id.clone(),
+                    copies
+                        .iter()
+                        .map(|(location, _, _)| location.clone())
+                        .collect(),
+                );
+            }
+
+            let chosen_copy = self
+                .preferred_mod_locations
+                .get(&package_id)
+                .and_then(|preferred_location| {
+                    copies
+                        .iter()
+                        .position(|(location, _, _)| location == preferred_location)
+                })
+                .unwrap_or(0);
+            let (location, about_file_xml, mod_files) = copies.swap_remove(chosen_copy);
+
+            let (package_id, mod_metadata) =
+                self.load_mod_metadata(&location, about_file_xml, mod_files, game_version);
+            self.inactive_mod_list.0.insert(package_id, mod_metadata);
+        }
+
+        // Keep the selection if the mod is still around
+        let selected_mod = self.currently_selected_mod.take();
+        self.currently_selected_mod =
+            selected_mod.filter(|package_id| self.inactive_mod_list.0.contains_key(package_id));
+
+        scan_result.errors
+    }
+
+    /// The version the mods are checked against, the override if there is one
+    pub fn game_version(&self) -> Option<GameVersion> {
+        self.game_version_override.or(self.installed_game_version)
+    }
+
+    /// Checks the mods against another version, or the installed one again with [None]
+    /// The rules mods declare depend on the version so everything is scanned again, keeping the active list
+    pub fn set_game_version_override(&mut self, game_version_override: Option<GameVersion>) {
+        if self.game_version_override == game_version_override {
+            return;
+        }
 
-        self.currently_selected_mod = None;
+        self.game_version_override = game_version_override;
+        self.rescan_keeping_active_list();
+    }
 
-        // Grab the game version
-        let mut version_file_path = self.game_path.clone().unwrap();
-        version_file_path.extend(["Version.txt"]);
-        let game_version_file = String::from_utf8(fs::read(version_file_path)?)?;
-        let game_version = parse_game_version(&game_version_file)?;
+    /// Uses another copy of a mod that is installed more than once
+    pub fn choose_mod_copy(&mut self, package_id: PackageId, location: PathBuf) {
+        self.preferred_mod_locations.insert(package_id, location);
+        // Only the copy in use was kept from the last scan
+        self.rescan_keeping_active_list();
+    }
 
+    /// Every folder mods are looked for in
+    pub fn scan_paths(&self) -> Vec<PathBuf> {
         let mut scan_paths = Vec::new();
 
-        // Base game data files
-        scan_paths.push(self.game_path.clone().unwrap().join("Data"));
-        // Normal Mod folder
-        scan_paths.push(self.game_path.clone().unwrap().join("Mods"));
-        // Steam mod folder
+        // The first copy found of a mod installed more than once is used by default, so the game folder goes first
+        if let Some(game_path) = &self.game_path {
+            // Base game data files
+            scan_paths.push(game_path.join("Data"));
+            // Normal Mod folder
+            scan_paths.push(game_path.join("Mods"));
+        }
+
+        scan_paths.extend(self.mod_folder_paths.iter().cloned());
+
+        // Steam mod folders, which can be in any of the prefix's libraries
         if let Some(steam_prefix) = &self.steam_path {
-            let path = steam_prefix
-                .join("steamapps")
-                .join("workshop")
-                .join("content")
-                .join("294100");
+            scan_paths.extend(find_workshop_paths(steam_prefix));
+        }
+
+        // The game's Mods folder or the workshop folder can also be added as an extra mod folder
+        let mut seen_paths = HashSet::new();
+        scan_paths
+            .retain(|path| seen_paths.insert(path.canonicalize().unwrap_or_else(|_| path.clone())));
+
+        scan_paths
+    }
+
+    /// Loads the rules a mod declares
+    fn load_mod_metadata(
+        &mut self,
+        mod_folder: &Path,
+        about_file_xml: ModMetaData,
+        mod_files: ModFiles,
+        game_version: GameVersion,
+    ) -> (PackageId, CondensedModMetadata) {
+        let supports_game_version = about_file_xml.does_mod_support_this_version(game_version);
+
+        if !supports_game_version {
+            log::info!(
+                "The mod located at {} doesn't declare support for this version",
+                mod_folder.display()
+            );
+        }
+
+        let unreadable_versions = about_file_xml.unreadable_versions();
+
+        if !unreadable_versions.is_empty() {
+            log::warn!(
+                "The mod located at {} lists versions that can't be read: {}",
+                mod_folder.display(),
+                unreadable_versions.join(", ")
+            );
+        }
+
+        let has_content_for_game_version = !mod_files
+            .content_layout
+            .lacks_content_for_version(game_version);
+
+        if !has_content_for_game_version {
+            log::info!(
+                "The mod located at {} has no folder for this version",
+                mod_folder.display()
+            );
+        }
+
+        about_file_xml.load_dependency_information_for_version(game_version, &mut self.mod_rules);
+
+        (
+            about_file_xml.package_id.clone(),
+            CondensedModMetadata {
+                supported_versions: about_file_xml.supported_version_names(),
+                supports_game_version,
+                unreadable_versions,
+                has_content_for_game_version,
+                dependencies: about_file_xml.dependencies_for_version(game_version),
+                // The author field is split on commas, leaving spaces behind
+                authors: about_file_xml
+                    .get_mod_authors()
+                    .iter()
+                    .map(|author| author.trim().to_owned())
+                    .filter(|author| !author.is_empty())
+                    .sorted()
+                    .dedup()
+                    .collect(),
+                mod_version: about_file_xml.mod_version,
+                url: about_file_xml.url,
+                published_file_id: mod_files.published_file_id,
+                preview_path: mod_files.preview_path,
+                icon_path: mod_files.icon_path,
+                content_layout: mod_files.content_layout,
+                displayable_name: about_file_xml
+                    .name
+                    .unwrap_or(about_file_xml.package_id.0.to_string()),
+                location: mod_folder.to_path_buf(),
+                source: self.mod_source(mod_folder),
+                description: about_file_xml.description,
+            },
+        )
+    }
+
+    /// Works out where a mod came from by the scan path its folder is in
+    fn mod_source(&self, mod_folder: &Path) -> ModSource {
+        let scan_path = mod_folder.parent();
+
+        if let Some(game_path) = &self.game_path {
+            if scan_path == Some(game_path.join("Data").as_path()) {
+                return ModSource::Official;
+            }
+
+            if scan_path == Some(game_path.join("Mods").as_path()) {
+                return ModSource::Local;
+            }
+        }
+
+        if scan_path.is_some_and(|scan_path| self.mod_folder_paths.contains(scan_path)) {
+            return ModSource::Custom;
+        }
 
-            if path.is_dir() {
-                scan_paths.push(path);
+        // Anything else came from one of the workshop folders of the Steam prefix
+        ModSource::Workshop
+    }
+
+    /// Moves the listed mods into the active list in order
+    /// Must be called after [RimManager::refresh_metadata] as it expects the active list to be empty
+    /// Returns the listed mods that aren't installed
+    pub fn load_mod_ordering<'a>(
+        &mut self,
+        mod_ordering: impl IntoIterator<Item = &'a PackageId>,
+    ) -> Vec<PackageId> {
+        let mut missing_mods = Vec::new();
+
+        // Check for mods in our known mods and add them
+        for mod_id in mod_ordering {
+            if let Some(mod_metadata) = self.inactive_mod_list.0.shift_remove(mod_id) {
+                self.active_mod_list.0.insert(mod_id.clone(), mod_metadata);
+            } else {
+                missing_mods.push(mod_id.clone());
             }
         }
 
-        // Look in the directories to scan
-        for scan_dir in self.mod_folder_paths.iter().chain(&scan_paths) {
-            // Find the folders of the mods
-            for mod_folder in scan_dir.read_dir()? {
-                // Get all the folders we can read
-                // TODO: Warn about folders we can't read? Can't imagine this being too much of a issue through
-                if let Ok(mod_folder) = mod_folder.map(|folder| folder.path()) {
-                    // Only interact with directories
-                    if !mod_folder.is_dir() {
-                        continue;
+        self.active_mod_list
+            .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
+
+        missing_mods
+    }
+
+    /// Scans the installation in the background and puts the current profile's mods back into the active list
+    pub fn rescan(&mut self) {
+        self.start_scan(AfterScan::ApplyProfile);
+    }
+
+    /// Scans the installation in the background and puts the mods that are active now back into the active list
+    pub fn rescan_keeping_active_list(&mut self) {
+        self.start_scan(AfterScan::KeepModOrdering(
+            self.active_mod_list.0.keys().cloned().collect(),
+        ));
+    }
+
+    /// If a scan is running whose result replaces the mod lists, so changing them now would be thrown away
+    /// Scans started by the watcher update the mods they reread in place and don't count
+    pub fn is_replacing_mod_lists(&self) -> bool {
+        self.scan_task
+            .as_ref()
+            .is_some_and(|(_, after_scan)| !matches!(after_scan, AfterScan::UpdateModFolders))
+    }
+
+    /// Starts scanning the installation on another thread
+    /// [RimManager::finish_scan] picks up the result
+    pub fn start_scan(&mut self, after_scan: AfterScan) {
+        let Some(game_path) = self.game_path.clone() else {
+            return;
+        };
+
+        self.scan_task = Some((
+            BackgroundScan::start(game_path, self.scan_paths()),
+            after_scan,
+        ));
+    }
+
+    /// Applies the result of the background scan once it is done
+    pub fn finish_scan(&mut self) {
+        if !self
+            .scan_task
+            .as_ref()
+            .is_some_and(|(scan, _)| scan.is_finished())
+        {
+            return;
+        }
+
+        let (scan, after_scan) = self.scan_task.take().unwrap();
+
+        let scan_result = match scan.join() {
+            Ok(scan_result) => scan_result,
+            Err(error) => {
+                self.notifications.push(error);
+                return;
+            }
+        };
+
+        match after_scan {
+            AfterScan::ApplyProfile => {
+                self.apply_full_scan(scan_result);
+                self.profile_missing_mods = self.apply_current_profile();
+            }
+            AfterScan::LoadModOrdering {
+                modconfig_xml_path,
+                mod_ordering,
+            } => {
+                self.apply_full_scan(scan_result);
+                let missing_mods = self.load_mod_ordering(&mod_ordering);
+                self.modconfig_xml_changed = false;
+
+                if !missing_mods.is_empty() {
+                    self.notifications.push(FileError::ModsNotInstalled {
+                        path: modconfig_xml_path,
+                        cause: anyhow!(
+                            "{}",
+                            missing_mods
+                                .iter()
+                                .map(|package_id| package_id.0.as_str())
+                                .join(", ")
+                        ),
+                    });
+                }
+            }
+            AfterScan::KeepModOrdering(mod_ordering) => {
+                self.apply_full_scan(scan_result);
+                self.load_mod_ordering(&mod_ordering);
+            }
+            AfterScan::UpdateModFolders => {
+                let game_version = self
+                    .game_version_override
+                    .unwrap_or(scan_result.game_version);
+
+                // Which copy of a duplicated mod gets used depends on all of them
+                let mut needs_full_scan = scan_result.mods.iter().any(|scanned_mod| {
+                    self.duplicate_mods
+                        .values()
+                        .flatten()
+                        .any(|location| *location == scanned_mod.location)
+                });
+
+                for scanned_mod in scan_result.mods {
+                    if needs_full_scan {
+                        break;
                     }
 
-                    log::info!(
-                        "Beginning inspection of mod located at: {}",
-                        mod_folder.display()
-                    );
+                    needs_full_scan = self.update_mod_folder(scanned_mod, game_version);
+                }
+
+                if needs_full_scan {
+                    self.rescan_keeping_active_list();
+                    return;
+                }
+
+                self.active_mod_list
+                    .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
+            }
+        }
+    }
+
+    /// Replaces the mod lists and starts watching everything the scan found
+    fn apply_full_scan(&mut self, scan_result: ScanResult) {
+        let scan_paths = scan_result.scan_paths.clone();
+        let mod_folders: Vec<_> = scan_result
+            .mods
+            .iter()
+            .map(|scanned_mod| scanned_mod.location.clone())
+            .collect();
+
+        let scan_errors = self.apply_scan_result(scan_result);
+        self.notifications.extend(scan_errors);
+        // Even broken mods get watched so fixing their About.xml is noticed
+        self.watch_installation(scan_paths, &mod_folders);
+    }
+
+    /// Rebuilds the active and inactive lists from the current profile
+    /// Returns the mods in the profile that aren't installed
+    pub fn apply_current_profile(&mut self) -> Vec<PackageId> {
+        let Some(profile) = self.profiles.current_profile() else {
+            return Vec::new();
+        };
+        let active_mods = profile.active_mods.clone();
+
+        // Put everything back into the inactive list first
+        let previously_active = std::mem::take(&mut self.active_mod_list.0);
+        self.inactive_mod_list.0.extend(previously_active);
+
+        self.load_mod_ordering(&active_mods)
+    }
+
+    /// Saves the active list into the current profile before switching to another one
+    pub fn switch_profile(&mut self, name: Option<String>) {
+        self.sync_current_profile();
+        self.profiles.current = name;
+        self.profile_missing_mods = self.apply_current_profile();
+    }
+
+    /// Copies the active list into the current profile
+    /// Mods that aren't installed are kept at the end so they aren't lost when a folder is missing for a moment
+    pub fn sync_current_profile(&mut self) {
+        // Nothing has been scanned so there is nothing to copy
+        if self.active_mod_list.0.is_empty() && self.inactive_mod_list.0.is_empty() {
+            return;
+        }
+
+        let Some(profile) = self.profiles.current_profile_mut() else {
+            return;
+        };
+
+        let mut active_mods: Vec<_> = self.active_mod_list.0.keys().cloned().collect();
+        active_mods.extend(
+            profile
+                .active_mods
+                .iter()
+                .filter(|package_id| {
+                    !self.active_mod_list.0.contains_key(*package_id)
+                        && !self.inactive_mod_list.0.contains_key(*package_id)
+                })
+                .cloned(),
+        );
+
+        profile.active_mods = active_mods;
+    }
+
+    pub fn save_profiles_if_changed(&mut self) {
+        self.sync_current_profile();
+
+        if self.profiles != self.saved_profiles {
+            log::info!("Saving profiles");
+
+            if let Err(error) = self.profiles.save() {
+                log::warn!("Can't save the profiles: {}", error);
+            }
 
-                    if let Ok(about_file_xml) = read_about_xml(&mod_folder) {
-                        if !about_file_xml.does_mod_support_this_version(game_version.clone()) {
-                            log::info!("Skipping mod");
-                            continue;
+            // Even on failure as we don't want to retry every frame
+            self.saved_profiles = self.profiles.clone();
+        }
+    }
+
+    pub fn create_profile_panel(&mut self, ctx: &egui::Context) {
+        egui::TopBottomPanel::top("profiles").show(ctx, |ui| {
+            // Whatever the scan puts in the lists ends up in the current profile, so it can't change under it
+            ui.set_enabled(!self.is_replacing_mod_lists());
+
+            ui.horizontal(|ui| {
+                let mut profile_to_switch_to = None;
+
+                ui.label("Profile");
+
+                egui::ComboBox::from_id_source("profile_picker")
+                    .selected_text(self.profiles.current.as_deref().unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        if ui
+                            .selectable_label(self.profiles.current.is_none(), "None")
+                            .clicked()
+                        {
+                            profile_to_switch_to = Some(None);
                         }
 
-                        about_file_xml.load_dependency_information_for_version(
-                            game_version.clone(),
-                            &mut self.mod_rules,
-                        );
+                        for name in self.profiles.profiles.keys() {
+                            if ui
+                                .selectable_label(
+                                    self.profiles.current.as_ref() == Some(name),
+                                    name,
+                                )
+                                .clicked()
+                            {
+                                profile_to_switch_to = Some(Some(name.clone()));
+                            }
+                        }
+                    });
 
-                        self.inactive_mod_list.0.insert(
-                            about_file_xml.package_id.clone(),
-                            CondensedModMetadata {
-                                displayable_name: about_file_xml
-                                    .name
-                                    .unwrap_or(about_file_xml.package_id.0.to_string()),
-                                location: mod_folder,
-                                description: about_file_xml.description,
-                            },
-                        );
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.profile_name_input)
+                        .hint_text("Profile name")
+                        .desired_width(150.0),
+                );
+
+                let new_name = self.profile_name_input.trim().to_owned();
+                let current = self.profiles.current.clone();
+
+                if ui.button("New").clicked() {
+                    // Starts from whatever is active right now
+                    let profile = Profile {
+                        active_mods: self.active_mod_list.0.keys().cloned().collect(),
+                        version_overrides: self.profiles.current_version_overrides().clone(),
+                    };
+
+                    self.profile_error = self.profiles.create(&new_name, profile).err();
+
+                    if self.profile_error.is_none() {
+                        self.sync_current_profile();
+                        self.profiles.current = Some(new_name.clone());
+                    }
+                }
+
+                if let Some(current) = &current {
+                    if ui.button("Rename").clicked() {
+                        self.profile_error = self.profiles.rename(current, &new_name).err();
+                    }
+
+                    if ui.button("Duplicate").clicked() {
+                        self.sync_current_profile();
+                        self.profile_error = self.profiles.duplicate(current, &new_name).err();
+
+                        if self.profile_error.is_none() {
+                            self.profiles.current = Some(new_name.clone());
+                        }
+                    }
+
+                    if ui.button("Delete").clicked() {
+                        self.profile_error = self.profiles.delete(current).err();
+                    }
+                }
+
+                if let Some(error) = &self.profile_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+                }
+
+                if let Some(name) = profile_to_switch_to {
+                    self.profile_error = None;
+                    self.switch_profile(name);
+                }
+            });
+
+            if !self.profile_missing_mods.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(format!(
+                        "These mods in the profile are not installed: {}",
+                        self.profile_missing_mods
+                            .iter()
+                            .map(|package_id| package_id.0.as_str())
+                            .join(", ")
+                    ));
+
+                    ui.add_enabled_ui(!self.is_replacing_mod_lists(), |ui| {
+                        if ui.button("Remove them from the profile").clicked() {
+                            let missing_mods = std::mem::take(&mut self.profile_missing_mods);
+
+                            if let Some(profile) = self.profiles.current_profile_mut() {
+                                profile
+                                    .active_mods
+                                    .retain(|package_id| !missing_mods.contains(package_id));
+                            }
+                        }
+                    });
+
+                    if ui.button("Dismiss").clicked() {
+                        self.profile_missing_mods.clear();
+                    }
+                });
+            }
+        });
+    }
+
+    pub fn create_mod_list_panel(
+        &mut self,
+        ctx: &egui::Context,
+        is_active_list: bool,
+        // The change and the changing problem
+    ) -> Option<PackageId> {
+        let mut currently_selected = None;
+        let is_scanning = self.is_replacing_mod_lists();
+
+        let (list_name, searcher, hidden_sources) = if is_active_list {
+            (
+                "active",
+                &mut self.active_search,
+                &mut self.active_hidden_sources,
+            )
+        } else {
+            (
+                "inactive",
+                &mut self.inactive_search,
+                &mut self.inactive_hidden_sources,
+            )
+        };
+
+        // Mod manager panel
+        egui::SidePanel::left(list_name.to_owned() + "_mod_list")
+            .resizable(true)
+            .show(ctx, |ui| {
+                ui.label(list_name.to_owned() + " mods");
+
+                ui.horizontal(|ui| {
+                    ui.label("🔎");
+                    ui.text_edit_singleline(searcher);
+
+                    let sources_text = if hidden_sources.is_empty() {
+                        "Sources"
                     } else {
-                        log::warn!("Can't parse this mods About.xml!");
+                        "Sources (filtered)"
+                    };
+
+                    ui.menu_button(sources_text, |ui| {
+                        for source in ModSource::ALL {
+                            let mut is_shown = !hidden_sources.contains(&source);
+
+                            if ui.checkbox(&mut is_shown, source.to_string()).changed() {
+                                if is_shown {
+                                    hidden_sources.remove(&source);
+                                } else {
+                                    hidden_sources.insert(source);
+                                }
+                            }
+                        }
+                    });
+                });
+                ui.separator();
+
+                // Dragging only makes sense when every mod in the list is shown
+                let is_filtered = !searcher.is_empty() || !hidden_sources.is_empty();
+
+                // Whatever is changed during a scan would be thrown away once it finishes
+                ui.add_enabled_ui(!is_scanning, |ui| {
+                    egui::ScrollArea::vertical()
+                        .id_source(list_name.to_owned() + "_mod_scroll_area")
+                        .show(ui, |ui| {
+                            ui.vertical(|ui| {
+                                let mut mod_to_change = None;
+
+                                let list_to_display = if is_active_list {
+                                    &self.active_mod_list
+                                } else {
+                                    &self.inactive_mod_list
+                                };
+
+                                let version_overrides = self.profiles.current_version_overrides();
+
+                                let drag_result = dnd(ui, list_name.to_owned() + "_mod_list").show(
+                                    list_to_display
+                                        .0
+                                        .iter()
+                                        .filter(|(_, package_metadeta)| {
+                                            !hidden_sources.contains(&package_metadeta.source)
+                                        })
+                                        .map(|(package_id, package_metadeta)| {
+                                            (package_id, &package_metadeta.displayable_name)
+                                        })
+                                        // Filter out items that don't match the search
+                                        .filter(|(_, displayable_name)| {
+                                            searcher.is_empty()
+                                                || displayable_name
+                                                    .to_ascii_lowercase()
+                                                    .contains(&searcher.to_ascii_lowercase())
+                                        }),
+                                    |ui, (item, displayable_name), handle, _| {
+                                        let mod_metadata = &list_to_display.0[item];
+
+                                        ui.horizontal(|ui| {
+                                            // Disallow drag and drop when the user is searching or filtering
+                                            if !is_filtered && !is_scanning {
+                                                handle.ui(ui, |ui| {
+                                                    ui.label("=");
+                                                });
+                                            }
+
+                                            let is_overridden = version_overrides.contains(item);
+
+                                            // Unsupported mods have to be allowed before they can be activated
+                                            let can_change = is_active_list
+                                                || mod_metadata.supports_game_version
+                                                || is_overridden;
+
+                                            if ui
+                                                .add_enabled(can_change, Button::new("↔"))
+                                                .on_disabled_hover_text(
+                                                    "This mod doesn't support this version of RimWorld. Allow it from the info panel first",
+                                                )
+                                                .clicked()
+                                            {
+                                                mod_to_change = Some(item.clone());
+                                            }
+
+                                            if is_active_list
+                                                && self.mod_list_issue_cache.0.contains_key(item)
+                                            {
+                                                ui.label("🚫");
+                                            }
+
+                                            if self.missing_mod_folders.contains(item) {
+                                                ui.label("⚠")
+                                                    .on_hover_text("The folder of this mod is gone");
+                                            }
+
+                                            if self.duplicate_mods.contains_key(item) {
+                                                ui.label("⧉").on_hover_text(format!(
+                                                    "Installed more than once, using the copy in {}",
+                                                    mod_metadata.location.display()
+                                                ));
+                                            }
+
+                                            if !mod_metadata.supports_game_version {
+                                                ui.colored_label(
+                                                    if is_active_list && !is_overridden {
+                                                        ui.visuals().warn_fg_color
+                                                    } else {
+                                                        ui.visuals().weak_text_color()
+                                                    },
+                                                    "unsupported version",
+                                                )
+                                                .on_hover_text(format_supported_versions(mod_metadata));
+                                            }
+
+                                            if !mod_metadata.has_content_for_game_version {
+                                                ui.colored_label(
+                                                    if is_active_list {
+                                                        ui.visuals().warn_fg_color
+                                                    } else {
+                                                        ui.visuals().weak_text_color()
+                                                    },
+                                                    "no version folder",
+                                                )
+                                                .on_hover_text(format_content_versions(mod_metadata));
+                                            }
+
+                                            ui.label(
+                                                egui::RichText::new(mod_metadata.source.to_string())
+                                                    .small()
+                                                    .weak(),
+                                            );
+
+                                            if ui
+                                                .add(
+                                                    egui::Button::new(displayable_name).wrap(true),
+                                                )
+                                                .clicked()
+                                            {
+                                                // Chance to cause failure
+                                                mod_to_change = None;
+                                                currently_selected = Some(item.clone());
+                                            }
+                                        });
+
+                                        ui.end_row();
+                                    },
+                                );
+
+                                if let Some(drag_result) = drag_result.final_update() {
+                                    let my_list = if is_active_list {
+                                        &mut self.active_mod_list
+                                    } else {
+                                        &mut self.inactive_mod_list
+                                    };
+
+                                    // This looks strange and hacky but it creates a more natural dragging operation
+                                    match drag_result.from.cmp(&drag_result.to) {
+                                        std::cmp::Ordering::Less => {
+                                            my_list.0.move_index(drag_result.from, drag_result.to - 1)
+                                        }
+                                        std::cmp::Ordering::Equal => (),
+                                        std::cmp::Ordering::Greater => {
+                                            my_list.0.move_index(drag_result.from, drag_result.to)
+                                        }
+                                    }
+
+                                    if is_active_list {
+                                        self.active_mod_list.find_list_issues(
+                                            &self.mod_rules,
+                                            &mut self.mod_list_issue_cache,
+                                        );
+                                    }
+                                }
+
+                                if let Some(mod_to_change) = mod_to_change {
+                                    if is_active_list {
+                                        let plan = self
+                                            .active_mod_list
+                                            .plan_deactivation(&self.mod_rules, &mod_to_change);
+
+                                        match self.deactivation_choice {
+                                            _ if plan.dependents.is_empty() => self
+                                                .active_mod_list
+                                                .deactivate(&mut self.inactive_mod_list, &plan, false),
+                                            Some(choice) => self.active_mod_list.deactivate(
+                                                &mut self.inactive_mod_list,
+                                                &plan,
+                                                choice == DeactivationChoice::WithDependents,
+                                            ),
+                                            None => self.pending_deactivation = Some(plan),
+                                        }
+                                    } else {
+                                        let plan = self.inactive_mod_list.plan_activation(
+                                            &self.mod_rules,
+                                            &self.active_mod_list,
+                                            &mod_to_change,
+                                            self.profiles.current_version_overrides(),
+                                        );
+
+                                        // Only bother the user when more than the one mod is involved
+                                        if plan.dependencies.is_empty() && plan.missing.is_empty() {
+                                            self.active_mod_list.activate(
+                                                &self.mod_rules,
+                                                &mut self.inactive_mod_list,
+                                                &plan,
+                                            );
+                                        } else {
+                                            self.pending_activation = Some(plan);
+                                        }
+                                    }
+
+                                    self.active_mod_list.find_list_issues(
+                                        &self.mod_rules,
+                                        &mut self.mod_list_issue_cache,
+                                    );
+                                }
+                            });
+                        });
+                });
+            });
+
+        currently_selected
+    }
+}
+
+impl RimManager {
+    pub fn create_rule_db_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_rule_db_window;
+        let mut changed = false;
+
+        egui::Window::new("Rule databases")
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                egui::Grid::new("rule_db_grid")
+                    .striped(true)
+                    .show(ui, |ui| {
+                        let mut to_reload = None;
+                        let mut to_toggle = None;
+
+                        for (db_type, db) in &self.mod_rules.dbs {
+                            let mut is_enabled = self.mod_rules.is_enabled(db_type);
+
+                            if ui.checkbox(&mut is_enabled, "").changed() {
+                                to_toggle = Some((db_type.clone(), is_enabled));
+                            }
+
+                            ui.label(match db_type {
+                                ModdbType::ModBuiltRules => "About.xml files".to_owned(),
+                                ModdbType::DefaultRules => "Default rules".to_owned(),
+                                ModdbType::RuleFile(path) => path.to_string_lossy().to_string(),
+                            });
+                            ui.label(format!("{} mods", db.len()));
+
+                            if matches!(db_type, ModdbType::RuleFile(_))
+                                && ui.button("Reload").clicked()
+                            {
+                                to_reload = Some(db_type.clone());
+                            }
+
+                            ui.end_row();
+                        }
+
+                        if let Some((db_type, is_enabled)) = to_toggle {
+                            self.mod_rules.set_enabled(&db_type, is_enabled);
+                            changed = true;
+                        }
+
+                        if let Some(db_type) = to_reload {
+                            self.rule_db_error = self
+                                .mod_rules
+                                .reload_db(&db_type)
+                                .map_err(|error| anyhow!("Can't reload {}: {:#}", db_type, error))
+                                .err();
+                            changed = true;
+                        }
+                    });
+
+                if let Some(error) = &self.rule_db_error {
+                    ui.colored_label(ui.visuals().error_fg_color, error.to_string());
+                }
+
+                ui.separator();
+
+                if let Some(rule_dir) = resolve_rule_dir() {
+                    ui.label(format!("Rule files are loaded from {}", rule_dir.display()));
+
+                    if ui.button("Load new rule files").clicked() {
+                        let rule_errors = self.load_rule_folder(&rule_dir);
+                        self.notifications.extend(rule_errors);
+                        changed = true;
+                    }
+                }
+            });
+
+        self.show_rule_db_window = is_open;
+
+        if changed {
+            self.active_mod_list
+                .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
+        }
+    }
+}
+
+impl RimManager {
+    pub fn refresh_backups(&mut self) {
+        self.backups = resolve_modconfig_xml_path()
+            .ok_or_else(|| anyhow!(FileError::MissingHomeDir))
+            .and_then(|modconfig_xml_path| list_backups(&modconfig_xml_path))
+            .unwrap_or_else(|error| {
+                log::warn!("Can't list the backups: {}", error);
+                Vec::new()
+            });
+    }
+
+    pub fn create_backup_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_backup_window;
+        let mut restored = false;
+
+        egui::Window::new("Backups")
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "A backup of ModsConfig.xml is taken before every save. The newest {} are kept",
+                    MAX_BACKUPS
+                ));
+
+                if let Some(status) = &self.backup_status {
+                    ui.label(status);
+                }
+
+                ui.separator();
+
+                if self.backups.is_empty() {
+                    ui.label("There are no backups yet");
+                }
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    egui::Grid::new("backup_grid").striped(true).show(ui, |ui| {
+                        for backup in &self.backups {
+                            ui.label(format_timestamp(backup.created) + " UTC");
+
+                            if ui.button("Restore").clicked() {
+                                let restored_backup = resolve_modconfig_xml_path()
+                                    .ok_or_else(|| anyhow!(FileError::MissingHomeDir))
+                                    .and_then(|modconfig_xml_path| {
+                                        restore_backup(backup, &modconfig_xml_path)
+                                    });
+
+                                self.backup_status = Some(match restored_backup {
+                                    Ok(()) => format!(
+                                        "Restored the backup from {} UTC. Load the mod ordering to see it",
+                                        format_timestamp(backup.created)
+                                    ),
+                                    Err(error) => format!("Can't restore the backup: {}", error),
+                                });
+                                restored = true;
+                            }
+
+                            ui.end_row();
+                        }
+                    });
+                });
+            });
+
+        self.show_backup_window = is_open;
+
+        // Restoring backs up the current file too
+        if restored {
+            self.refresh_backups();
+        }
+    }
+}
+
+impl RimManager {
+    /// Starts watching the scan folders, the mod folders in them and ModsConfig.xml
+    fn watch_installation(&mut self, scan_paths: Vec<PathBuf>, mod_folders: &[PathBuf]) {
+        self.mod_watcher = match ModWatcher::new(scan_paths, resolve_modconfig_xml_path()) {
+            Ok(mut watcher) => {
+                for mod_folder in mod_folders {
+                    watcher.watch_mod_folder(mod_folder);
+                }
+
+                Some(watcher)
+            }
+            Err(error) => {
+                log::warn!("Can't watch the mod folders: {}", error);
+                None
+            }
+        };
+    }
+
+    /// The mod loaded from this folder, if any
+    fn find_mod_by_location(&self, mod_folder: &Path) -> Option<PackageId> {
+        self.active_mod_list
+            .0
+            .iter()
+            .chain(&self.inactive_mod_list.0)
+            .find(|(_, mod_metadata)| mod_metadata.location == mod_folder)
+            .map(|(package_id, _)| package_id.clone())
+    }
+
+    /// Rereads a single mod folder after it was added, removed or changed
+    /// Active mods whose folder went away stay in the list but are flagged so the user can decide what to do
+    /// Returns if the folder turned out to hold another copy of an installed mod, which needs a full scan to sort out
+    fn update_mod_folder(&mut self, scanned_mod: ScannedMod, game_version: GameVersion) -> bool {
+        let mod_folder = scanned_mod.location.as_path();
+        let previous_package_id = self.find_mod_by_location(mod_folder);
+
+        // Forget the rules the old About.xml declared
+        if let Some(previous_package_id) = &previous_package_id {
+            if let Some(mod_built_rules) = self.mod_rules.dbs.get_mut(&ModdbType::ModBuiltRules) {
+                mod_built_rules.remove(previous_package_id);
+            }
+        }
+
+        self.broken_mods.shift_remove(mod_folder);
+
+        let new_mod = match scanned_mod.about {
+            Ok((about_file_xml, mod_files)) => {
+                Some(self.load_mod_metadata(mod_folder, about_file_xml, mod_files, game_version))
+            }
+            // A deleted folder is expected here, anything else goes with the broken mods
+            Err(broken_mod) if mod_folder.is_dir() => {
+                self.broken_mods
+                    .insert(mod_folder.to_path_buf(), broken_mod);
+                None
+            }
+            Err(_) => None,
+        };
+
+        if let Some((package_id, _)) = &new_mod {
+            if previous_package_id.as_ref() != Some(package_id)
+                && (self.active_mod_list.0.contains_key(package_id)
+                    || self.inactive_mod_list.0.contains_key(package_id))
+            {
+                return true;
+            }
+        }
+
+        if let Some(previous_package_id) = previous_package_id {
+            if new_mod.as_ref().map(|(package_id, _)| package_id) != Some(&previous_package_id) {
+                if self.active_mod_list.0.contains_key(&previous_package_id) {
+                    log::info!("The folder of {} is gone", previous_package_id.0);
+                    self.missing_mod_folders.insert(previous_package_id);
+                } else {
+                    self.inactive_mod_list.0.shift_remove(&previous_package_id);
+
+                    if self.currently_selected_mod.as_ref() == Some(&previous_package_id) {
+                        self.currently_selected_mod = None;
                     }
                 }
             }
         }
 
+        if let Some((package_id, mod_metadata)) = new_mod {
+            self.missing_mod_folders.remove(&package_id);
+
+            // Inserting over an existing key keeps its place in the list
+            if let Some(existing) = self.active_mod_list.0.get_mut(&package_id) {
+                *existing = mod_metadata;
+            } else {
+                self.inactive_mod_list.0.insert(package_id, mod_metadata);
+            }
+        }
+
+        if let Some(watcher) = &mut self.mod_watcher {
+            if mod_folder.is_dir() {
+                watcher.watch_mod_folder(mod_folder);
+            } else {
+                watcher.unwatch_mod_folder(mod_folder);
+            }
+        }
+
+        false
+    }
+
+    /// Applies whatever the watcher saw since the last frame
+    /// Changed mod folders are reread in the background, after the current scan if one is running
+    pub fn apply_watched_changes(&mut self) {
+        self.start_pending_mod_folder_scan();
+
+        let Some(watcher) = &self.mod_watcher else {
+            return;
+        };
+
+        let changes = watcher.poll();
+
+        if changes.is_empty() {
+            return;
+        }
+
+        if changes.modconfig_xml_changed {
+            // Our own saves end up here too, so only bother the user if the list differs from ours
+            if let Ok(mod_config_data) = read_modconfig_xml() {
+                self.modconfig_xml_changed = !mod_config_data
+                    .active_mods
+                    .list
+                    .iter()
+                    .eq(self.active_mod_list.0.keys());
+            }
+        }
+
+        for mod_folder in changes.mod_folders {
+            log::info!("Change detected in {}", mod_folder.display());
+
+            if !self.pending_mod_folders.contains(&mod_folder) {
+                self.pending_mod_folders.push(mod_folder);
+            }
+        }
+
+        self.start_pending_mod_folder_scan();
+    }
+
+    fn start_pending_mod_folder_scan(&mut self) {
+        if self.scan_task.is_some() || self.pending_mod_folders.is_empty() {
+            return;
+        }
+
+        let Some(game_path) = self.game_path.clone() else {
+            return;
+        };
+
+        self.scan_task = Some((
+            BackgroundScan::start_mod_folders(
+                game_path,
+                std::mem::take(&mut self.pending_mod_folders),
+            ),
+            AfterScan::UpdateModFolders,
+        ));
+    }
+
+    /// Rescans and replaces the active list with the one in ModsConfig.xml
+    pub fn load_modconfig_xml_ordering(&mut self) -> Result<(), FileError> {
+        let modconfig_xml_path = resolve_modconfig_xml_path().ok_or(FileError::MissingHomeDir)?;
+        let mod_ordering = read_modconfig_xml_from(&modconfig_xml_path)?;
+        self.start_scan(AfterScan::LoadModOrdering {
+            modconfig_xml_path,
+            mod_ordering: mod_ordering.active_mods.list.into_iter().collect(),
+        });
+
         Ok(())
     }
 
-    pub fn create_mod_list_panel(
-        &mut self,
-        ctx: &egui::Context,
-        is_active_list: bool,
-        // The change and the changing problem
-    ) -> Option<PackageId> {
-        let mut currently_selected = None;
-
-        let (list_name, searcher) = if is_active_list {
-            ("active", &mut self.active_search)
-        } else {
-            ("inactive", &mut self.inactive_search)
-        };
+    /// Writes the active list into ModsConfig.xml
+    pub fn save_mod_ordering(&mut self) -> Result<(), FileError> {
+        let mut mod_config_data = read_modconfig_xml_or_default()?;
+        self.fill_modconfig_xml(&mut mod_config_data);
+
+        write_modconfig_xml(&mod_config_data)
+    }
+
+    /// Puts the active list into ModsConfig.xml before it is written
+    pub fn fill_modconfig_xml(&self, mod_config_data: &mut ModsConfigData) {
+        // The game fills this in itself, but only once it has written the file
+        if mod_config_data.version.is_empty() {
+            if let Some(installed_game_version) = self.installed_game_version {
+                mod_config_data.version = installed_game_version.to_string();
+            }
+        }
+
+        mod_config_data.active_mods.list = self.active_mod_list.0.keys().cloned().collect();
+    }
+
+    /// Warns about active mods that don't declare support for the game version
+    /// Fixing the list can activate dependencies like these too, so it isn't enough to stop the user moving them
+    pub fn create_unsupported_mods_banner(&mut self, ctx: &egui::Context) {
+        let unsupported_mods = self
+            .active_mod_list
+            .find_unsupported_mods(self.profiles.current_version_overrides());
+
+        if unsupported_mods.is_empty() {
+            return;
+        }
+
+        egui::TopBottomPanel::top("unsupported_mods_banner").show(ctx, |ui| {
+            ui.horizontal_wrapped(|ui| {
+                ui.colored_label(
+                    ui.visuals().warn_fg_color,
+                    format!(
+                        "These active mods don't declare support for RimWorld {}: {}",
+                        self.game_version()
+                            .as_ref()
+                            .map(ToString::to_string)
+                            .unwrap_or_default(),
+                        unsupported_mods
+                            .iter()
+                            .map(|package_id| package_id.0.as_str())
+                            .join(", ")
+                    ),
+                );
+
+                if ui.button("Allow them in this profile").clicked() {
+                    self.profiles
+                        .current_version_overrides_mut()
+                        .extend(unsupported_mods.iter().cloned());
+                }
+            });
+        });
+    }
+
+    /// Lets the user check the mods against any version a mod says it supports
+    pub fn create_game_version_picker(&mut self, ui: &mut egui::Ui) {
+        let installed_text = format!(
+            "Installed ({})",
+            self.installed_game_version
+                .as_ref()
+                .map(ToString::to_string)
+                .unwrap_or_else(|| "unknown".to_owned())
+        );
+        let mut chosen_version = self.game_version_override;
+
+        egui::ComboBox::from_id_source("game_version_picker")
+            .selected_text(match &self.game_version_override {
+                Some(game_version_override) => game_version_override.to_string(),
+                None => installed_text.clone(),
+            })
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut chosen_version, None, installed_text);
+
+                let known_versions: BTreeSet<_> = self
+                    .active_mod_list
+                    .0
+                    .values()
+                    .chain(self.inactive_mod_list.0.values())
+                    .flat_map(|mod_metadata| &mod_metadata.supported_versions)
+                    .filter_map(|version| GameVersion::parse_major_minor(version).ok())
+                    .collect();
+
+                for version in known_versions {
+                    let version_text = version.to_string();
+                    ui.selectable_value(&mut chosen_version, Some(version), version_text);
+                }
+            });
+
+        self.set_game_version_override(chosen_version);
+    }
+
+    /// Tells the user about changes made behind our back
+    pub fn create_change_banner(&mut self, ctx: &egui::Context) {
+        if !self.modconfig_xml_changed && self.missing_mod_folders.is_empty() {
+            return;
+        }
+
+        egui::TopBottomPanel::top("change_banner").show(ctx, |ui| {
+            if self.modconfig_xml_changed {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("ModsConfig.xml was changed outside of rimmanager");
+
+                    if ui.button("Load it").clicked() {
+                        if let Err(error) = self.load_modconfig_xml_ordering() {
+                            self.notifications.push(error);
+                        }
+                    }
+
+                    if ui.button("Dismiss").clicked() {
+                        self.modconfig_xml_changed = false;
+                    }
+                });
+            }
+
+            if !self.missing_mod_folders.is_empty() {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label(format!(
+                        "The folders of these active mods are gone: {}",
+                        self.missing_mod_folders
+                            .iter()
+                            .map(|package_id| package_id.0.as_str())
+                            .sorted()
+                            .join(", ")
+                    ));
+
+                    ui.add_enabled_ui(!self.is_replacing_mod_lists(), |ui| {
+                        if ui.button("Deactivate them").clicked() {
+                            for package_id in self.missing_mod_folders.drain() {
+                                self.active_mod_list.0.shift_remove(&package_id);
+
+                                if self.currently_selected_mod.as_ref() == Some(&package_id) {
+                                    self.currently_selected_mod = None;
+                                }
+                            }
+
+                            self.active_mod_list
+                                .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
+                        }
+                    });
+                });
+            }
+        });
+    }
+}
+
+impl RimManager {
+    pub fn create_broken_mods_window(&mut self, ctx: &egui::Context) {
+        let mut is_open = self.show_broken_mods_window;
+
+        egui::Window::new("Broken mods")
+            .open(&mut is_open)
+            .show(ctx, |ui| {
+                ui.label("These mods have an About.xml that can't be read, so they can't be used");
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (location, broken_mod) in &self.broken_mods {
+                        ui.strong(
+                            broken_mod
+                                .name
+                                .clone()
+                                .or_else(|| {
+                                    location
+                                        .file_name()
+                                        .map(|name| name.to_string_lossy().to_string())
+                                })
+                                .unwrap_or_default(),
+                        );
+
+                        if let Some(package_id) = &broken_mod.package_id {
+                            ui.label(format!("packageId: {}", package_id.0));
+                        }
+
+                        ui.label(format!("Folder: {}", location.display()));
+                        ui.colored_label(
+                            ui.visuals().error_fg_color,
+                            format!("{:#}", broken_mod.error),
+                        );
+                        ui.separator();
+                    }
+                });
+            });
+
+        self.show_broken_mods_window = is_open;
+    }
+
+    /// Moves a mod and the dependencies in its plan into the active list
+    pub fn activate_mods(&mut self, plan: &ActivationPlan) {
+        self.active_mod_list
+            .activate(&self.mod_rules, &mut self.inactive_mod_list, plan);
+        self.active_mod_list
+            .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
+    }
+
+    /// Name of an installed mod, or its package id if it isn't installed
+    fn mod_display_name<'a>(&'a self, package_id: &'a PackageId) -> &'a str {
+        self.active_mod_list
+            .0
+            .get(package_id)
+            .or_else(|| self.inactive_mod_list.0.get(package_id))
+            .map(|mod_metadata| mod_metadata.displayable_name.as_str())
+            .unwrap_or(package_id.0.as_str())
+    }
+
+    /// Asks before activating a mod along with the mods it depends on
+    pub fn create_activation_modal(&mut self, ctx: &egui::Context) {
+        let activation_modal = Modal::new(ctx, "activation_modal");
+        let mut is_confirmed = false;
+        let mut is_cancelled = false;
+        let mut allow_unsupported = self.allow_unsupported_dependencies;
+
+        activation_modal.show(|ui| {
+            let Some(plan) = &self.pending_activation else {
+                return;
+            };
+
+            activation_modal.title(
+                ui,
+                format!("Activate {}", self.mod_display_name(&plan.package_id)),
+            );
+            activation_modal.frame(ui, |ui| {
+                if !plan.dependencies.is_empty() {
+                    ui.label("These mods it depends on will be activated too:");
+
+                    for dependency in &plan.dependencies {
+                        ui.label(format!(
+                            "• {} ({})",
+                            self.mod_display_name(dependency),
+                            dependency.0
+                        ));
+                    }
+                }
+
+                if !plan.unsupported.is_empty() {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        format!(
+                            "These dependencies don't declare support for RimWorld {}:",
+                            self.game_version()
+                                .map(|game_version| game_version.major_minor().to_string())
+                                .unwrap_or_default()
+                        ),
+                    );
+
+                    for dependency in &plan.unsupported {
+                        ui.label(format!(
+                            "• {} ({})",
+                            self.mod_display_name(dependency),
+                            dependency.0
+                        ));
+                    }
+
+                    ui.checkbox(&mut allow_unsupported, "Allow them in this profile");
+                }
+
+                if !plan.missing.is_empty() {
+                    ui.colored_label(
+                        ui.visuals().warn_fg_color,
+                        "These dependencies aren't installed:",
+                    );
+
+                    for (package_id, dependency) in &plan.missing {
+                        let dependency_info = self
+                            .active_mod_list
+                            .0
+                            .get(package_id)
+                            .or_else(|| self.inactive_mod_list.0.get(package_id))
+                            .and_then(|mod_metadata| {
+                                mod_metadata
+                                    .dependencies
+                                    .iter()
+                                    .find(|info| &info.package_id == dependency)
+                            });
+
+                        ui.horizontal_wrapped(|ui| {
+                            ui.label(format!(
+                                "• {} ({}), needed by {}",
+                                dependency_info
+                                    .map(|info| info.display_name.as_str())
+                                    .unwrap_or(dependency.0.as_str()),
+                                dependency.0,
+                                self.mod_display_name(package_id)
+                            ));
+
+                            if let Some(url) =
+                                dependency_info.and_then(|info| info.steam_workshop_url.as_ref())
+                            {
+                                ui.hyperlink_to("Workshop page", url.as_str());
+                            }
+                        });
+                    }
+                }
+            });
+            activation_modal.buttons(ui, |ui| {
+                let is_allowed = plan.unsupported.is_empty() || allow_unsupported;
+
+                ui.add_enabled_ui(!self.is_replacing_mod_lists() && is_allowed, |ui| {
+                    if activation_modal.suggested_button(ui, "Activate").clicked() {
+                        is_confirmed = true;
+                    }
+                });
+
+                if activation_modal.button(ui, "Cancel").clicked() {
+                    is_cancelled = true;
+                }
+            });
+
+            if activation_modal.was_outside_clicked() {
+                activation_modal.close();
+                is_cancelled = true;
+            }
+        });
+
+        self.allow_unsupported_dependencies = allow_unsupported;
+
+        if is_confirmed {
+            if let Some(plan) = self.pending_activation.take() {
+                self.profiles
+                    .current_version_overrides_mut()
+                    .extend(plan.unsupported.iter().cloned());
+                self.activate_mods(&plan);
+            }
+            self.allow_unsupported_dependencies = false;
+        } else if is_cancelled {
+            self.pending_activation = None;
+            self.allow_unsupported_dependencies = false;
+        } else if self.pending_activation.is_some() && !activation_modal.is_open() {
+            activation_modal.open();
+        }
+    }
+
+    /// Asks what to do with the active mods depending on a mod being deactivated
+    pub fn create_deactivation_modal(&mut self, ctx: &egui::Context) {
+        let deactivation_modal = Modal::new(ctx, "deactivation_modal");
+        let mut choice = None;
+        let mut is_cancelled = false;
+        let mut remember_choice = self.remember_deactivation_choice;
+
+        deactivation_modal.show(|ui| {
+            let Some(plan) = &self.pending_deactivation else {
+                return;
+            };
+
+            deactivation_modal.title(
+                ui,
+                format!("Deactivate {}", self.mod_display_name(&plan.package_id)),
+            );
+            deactivation_modal.frame(ui, |ui| {
+                ui.label("These active mods depend on it and will have issues without it:");
+
+                for dependent in &plan.dependents {
+                    ui.label(format!(
+                        "• {} ({})",
+                        self.mod_display_name(dependent),
+                        dependent.0
+                    ));
+                }
+
+                ui.checkbox(
+                    &mut remember_choice,
+                    "Don't ask again until rimmanager is closed",
+                );
+            });
+            deactivation_modal.buttons(ui, |ui| {
+                if deactivation_modal.button(ui, "Cancel").clicked() {
+                    is_cancelled = true;
+                }
+
+                ui.add_enabled_ui(!self.is_replacing_mod_lists(), |ui| {
+                    if deactivation_modal
+                        .caution_button(ui, "Leave them active")
+                        .clicked()
+                    {
+                        choice = Some(DeactivationChoice::Alone);
+                    }
+
+                    if deactivation_modal
+                        .suggested_button(ui, "Deactivate them too")
+                        .clicked()
+                    {
+                        choice = Some(DeactivationChoice::WithDependents);
+                    }
+                });
+            });
+
+            if deactivation_modal.was_outside_clicked() {
+                deactivation_modal.close();
+                is_cancelled = true;
+            }
+        });
+
+        self.remember_deactivation_choice = remember_choice;
+
+        if let Some(choice) = choice {
+            if remember_choice {
+                self.deactivation_choice = Some(choice);
+            }
+
+            if let Some(plan) = self.pending_deactivation.take() {
+                self.active_mod_list.deactivate(
+                    &mut self.inactive_mod_list,
+                    &plan,
+                    choice == DeactivationChoice::WithDependents,
+                );
+                self.active_mod_list
+                    .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
+            }
+        } else if is_cancelled {
+            self.pending_deactivation = None;
+        } else if self.pending_deactivation.is_some() && !deactivation_modal.is_open() {
+            deactivation_modal.open();
+        }
+    }
+
+    /// Lets the user pick which copy of a mod installed more than once gets used
+    pub fn create_duplicate_mods_window(&mut self, ctx: &egui::Context, can_use_mod_lists: bool) {
+        let mut is_open = self.show_duplicate_mods_window;
+        let mut chosen_copy = None;
 
-        // Mod manager panel
-        egui::SidePanel::left(list_name.to_owned() + "_mod_list")
-            .resizable(true)
+        egui::Window::new("Duplicate mods")
+            .open(&mut is_open)
             .show(ctx, |ui| {
-                ui.label(list_name.to_owned() + " mods");
-
-                ui.horizontal(|ui| {
-                    ui.label("🔎");
-                    ui.text_edit_singleline(searcher);
-                });
+                ui.label("These mods were found in more than one place. Only one copy of each can be used");
                 ui.separator();
 
-                egui::ScrollArea::vertical()
-                    .id_source(list_name.to_owned() + "_mod_scroll_area")
-                    .show(ui, |ui| {
-                        ui.vertical(|ui| {
-                            let mut mod_to_change = None;
-
-                            let list_to_display = if is_active_list {
-                                &self.active_mod_list
-                            } else {
-                                &self.inactive_mod_list
-                            };
-
-                            let drag_result = dnd(ui, list_name.to_owned() + "_mod_list").show(
-                                list_to_display
-                                    .0
-                                    .iter()
-                                    .map(|(package_id, package_metadeta)| {
-                                        (package_id, &package_metadeta.displayable_name)
-                                    })
-                                    // Filter out items that don't match the search
-                                    .filter(|(_, displayable_name)| {
-                                        searcher.is_empty()
-                                            || displayable_name
-                                                .to_ascii_lowercase()
-                                                .contains(&searcher.to_ascii_lowercase())
-                                    }),
-                                |ui, (item, displayable_name), handle, _| {
-                                    ui.horizontal(|ui| {
-                                        // Disallow drag and drop when the user is searching
-                                        if searcher.is_empty() {
-                                            handle.ui(ui, |ui| {
-                                                ui.label("=");
-                                            });
-                                        }
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for (package_id, locations) in &self.duplicate_mods {
+                        let mod_metadata = self
+                            .active_mod_list
+                            .0
+                            .get(package_id)
+                            .or_else(|| self.inactive_mod_list.0.get(package_id));
 
-                                        if ui.button("↔").clicked() {
-                                            mod_to_change = Some(item.clone());
-                                        }
+                        ui.strong(
+                            mod_metadata
+                                .map(|mod_metadata| mod_metadata.displayable_name.as_str())
+                                .unwrap_or(package_id.0.as_str()),
+                        );
 
-                                        if is_active_list
-                                            && self.mod_list_issue_cache.0.contains_key(item)
-                                        {
-                                            ui.label("🚫");
-                                        }
+                        ui.add_enabled_ui(can_use_mod_lists, |ui| {
+                            for location in locations {
+                                let is_in_use = mod_metadata
+                                    .is_some_and(|mod_metadata| &mod_metadata.location == location);
+
+                                if ui
+                                    .radio(is_in_use, location.to_string_lossy())
+                                    .clicked()
+                                    && !is_in_use
+                                {
+                                    chosen_copy = Some((package_id.clone(), location.clone()));
+                                }
+                            }
+                        });
 
-                                        if ui
-                                            .add(egui::Button::new(displayable_name).wrap(true))
-                                            .clicked()
-                                        {
-                                            // Chance to cause failure
-                                            mod_to_change = None;
-                                            currently_selected = Some(item.clone());
-                                        }
-                                    });
+                        ui.separator();
+                    }
+                });
+            });
 
-                                    ui.end_row();
-                                },
-                            );
+        self.show_duplicate_mods_window = is_open;
 
-                            if let Some(drag_result) = drag_result.final_update() {
-                                let my_list = if is_active_list {
-                                    &mut self.active_mod_list
-                                } else {
-                                    &mut self.inactive_mod_list
-                                };
+        if let Some((package_id, location)) = chosen_copy {
+            self.choose_mod_copy(package_id, location);
+        }
+    }
 
-                                // This looks strange and hacky but it creates a more natural dragging operation
-                                match drag_result.from.cmp(&drag_result.to) {
-                                    std::cmp::Ordering::Less => {
-                                        my_list.0.move_index(drag_result.from, drag_result.to - 1)
-                                    }
-                                    std::cmp::Ordering::Equal => (),
-                                    std::cmp::Ordering::Greater => {
-                                        my_list.0.move_index(drag_result.from, drag_result.to)
-                                    }
-                                }
+    /// Lists everything that failed until the user dismisses it
+    pub fn create_notification_panel(&mut self, ctx: &egui::Context) {
+        if self.notifications.is_empty() {
+            return;
+        }
 
-                                if is_active_list {
-                                    self.active_mod_list.find_list_issues(
-                                        &self.mod_rules,
-                                        &mut self.mod_list_issue_cache,
-                                    );
-                                }
-                            }
+        egui::TopBottomPanel::bottom("notifications").show(ctx, |ui| {
+            let mut to_dismiss = None;
 
-                            if let Some(mod_to_change) = mod_to_change {
-                                let (other_list, my_list) = if is_active_list {
-                                    (&mut self.inactive_mod_list, &mut self.active_mod_list)
-                                } else {
-                                    (&mut self.active_mod_list, &mut self.inactive_mod_list)
-                                };
+            ui.horizontal(|ui| {
+                ui.label(format!("{} problems", self.notifications.len()));
 
-                                other_list.0.insert(
-                                    mod_to_change.clone(),
-                                    my_list.0.shift_remove(&mod_to_change).unwrap(),
-                                );
+                if ui.button("Dismiss all").clicked() {
+                    self.notifications.clear();
+                }
+            });
 
-                                self.active_mod_list.find_list_issues(
-                                    &self.mod_rules,
-                                    &mut self.mod_list_issue_cache,
+            egui::ScrollArea::vertical()
+                .max_height(150.0)
+                .show(ui, |ui| {
+                    egui::Grid::new("notification_grid")
+                        .striped(true)
+                        .show(ui, |ui| {
+                            for (index, error) in self.notifications.iter().enumerate() {
+                                ui.colored_label(ui.visuals().error_fg_color, error.summary());
+                                ui.label(
+                                    error
+                                        .path()
+                                        .map(|path| path.display().to_string())
+                                        .unwrap_or_default(),
                                 );
+                                ui.label(
+                                    error
+                                        .cause()
+                                        .map(|cause| format!("{:#}", cause))
+                                        .unwrap_or_default(),
+                                );
+
+                                if ui.button("Dismiss").clicked() {
+                                    to_dismiss = Some(index);
+                                }
+
+                                ui.end_row();
                             }
                         });
-                    });
-            });
+                });
 
-        currently_selected
+            if let Some(index) = to_dismiss {
+                self.notifications.remove(index);
+            }
+        });
     }
 }
 
 impl eframe::App for RimManager {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.finish_scan();
+        self.apply_watched_changes();
+
+        // Keep the progress bar moving
+        if self.scan_task.is_some() {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+
+        // Nothing else wakes us up when the watcher sees something
+        if self.mod_watcher.is_some() {
+            ctx.request_repaint_after(Duration::from_secs(1));
+        }
+
         // Modal for when a the user tries to save a mod list without the core mod
         let missing_core_on_modlist_modal = alert_box(
             ctx,
@@ -285,50 +1894,47 @@ impl eframe::App for RimManager {
             "The path you selected does not represent a valid Steam prefix!",
         );
 
-        let unfixable_modlist_modal = alert_box(
+        let unfixable_modlist_modal = alert_box_with_id(
             ctx,
-            "The mod list has dependencies not installed, incompatible mods in the active list, or a direct circular dependency. Aborting sorting",
+            "unfixable_modlist_modal",
+            &format!(
+                "The mod list could not be sorted. {}",
+                self.sort_error
+                    .as_ref()
+                    .map(ToString::to_string)
+                    .unwrap_or_default()
+            ),
         );
 
+        // Don't let the user act on lists that are about to be replaced
+        let can_use_mod_lists = self.game_path.is_some() && !self.is_replacing_mod_lists();
+
         egui::TopBottomPanel::top("manager").show(ctx, |ui| {
             ui.horizontal(|ui| {
                 egui::Grid::new("button_grid").striped(true).show(ui, |ui| {
                     // Only enable the ability to scan installation once the user sets a game path
                     if ui
-                        .add_enabled(self.game_path.is_some(), Button::new("Scan installation"))
+                        .add_enabled(can_use_mod_lists, Button::new("Scan installation"))
                         .clicked()
                     {
-                        self.refresh_metadata().unwrap();
+                        self.rescan();
                     }
 
                     ui.end_row();
 
                     if ui
-                        .add_enabled(self.game_path.is_some(), Button::new("Load mod ordering"))
+                        .add_enabled(can_use_mod_lists, Button::new("Load mod ordering"))
                         .clicked()
                     {
-                        let mod_ordering = read_modconfig_xml().unwrap();
-                        self.refresh_metadata().unwrap();
-                        // Active mod list will be empty by here
-
-                        // Check for mods in our known mods and add them
-                        for mod_id in &mod_ordering.active_mods.list {
-                            if self.inactive_mod_list.0.contains_key(mod_id) {
-                                self.active_mod_list.0.insert(
-                                    mod_id.clone(),
-                                    self.inactive_mod_list.0.shift_remove(mod_id).unwrap(),
-                                );
-                            }
+                        if let Err(error) = self.load_modconfig_xml_ordering() {
+                            self.notifications.push(error);
                         }
-
-                        self.active_mod_list
-                            .find_list_issues(&self.mod_rules, &mut self.mod_list_issue_cache);
                     }
 
                     ui.end_row();
 
                     if ui
-                        .add_enabled(self.game_path.is_some(), Button::new("Save mod ordering"))
+                        .add_enabled(can_use_mod_lists, Button::new("Save mod ordering"))
                         .clicked()
                     {
                         if !self
@@ -339,28 +1945,75 @@ impl eframe::App for RimManager {
                             missing_core_on_modlist_modal.open();
                         } else if !self.mod_list_issue_cache.0.is_empty() {
                             mod_list_unresolved_issues_modal.open();
-                        } else {
-                            let mut mod_config_data = read_modconfig_xml().unwrap();
-                            mod_config_data.active_mods.list =
-                                self.active_mod_list.0.keys().cloned().collect();
-
-                            write_modconfig_xml(&mod_config_data).unwrap();
+                        } else if let Err(error) = self.save_mod_ordering() {
+                            self.notifications.push(error);
                         }
                     }
 
                     if ui
                         .add_enabled(
-                            !self.mod_list_issue_cache.0.is_empty(),
+                            can_use_mod_lists && !self.mod_list_issue_cache.0.is_empty(),
                             Button::new("Fix mod ordering"),
                         )
                         .clicked()
-                        && !self.active_mod_list.autofix(
-                            &self.mod_rules,
-                            &mut self.inactive_mod_list,
-                            &mut self.mod_list_issue_cache,
+                    {
+                        self.sort_error = self
+                            .active_mod_list
+                            .autofix(
+                                &self.mod_rules,
+                                &mut self.inactive_mod_list,
+                                &mut self.mod_list_issue_cache,
+                            )
+                            .err();
+
+                        if self.sort_error.is_some() {
+                            unfixable_modlist_modal.open();
+                        }
+                    }
+
+                    ui.end_row();
+
+                    if ui.button("Rule databases").clicked() {
+                        self.show_rule_db_window = !self.show_rule_db_window;
+                    }
+
+                    if ui.button("Backups").clicked() {
+                        self.show_backup_window = !self.show_backup_window;
+                        self.refresh_backups();
+                    }
+
+                    if ui
+                        .add_enabled(
+                            self.deactivation_choice.is_some(),
+                            Button::new("Ask about dependents again"),
+                        )
+                        .on_hover_text(
+                            "Ask again what to do with the mods depending on a mod being deactivated",
+                        )
+                        .clicked()
+                    {
+                        self.deactivation_choice = None;
+                        self.remember_deactivation_choice = false;
+                    }
+
+                    if ui
+                        .add_enabled(
+                            !self.broken_mods.is_empty(),
+                            Button::new(format!("Broken mods ({})", self.broken_mods.len())),
+                        )
+                        .clicked()
+                    {
+                        self.show_broken_mods_window = !self.show_broken_mods_window;
+                    }
+
+                    if ui
+                        .add_enabled(
+                            !self.duplicate_mods.is_empty(),
+                            Button::new(format!("Duplicate mods ({})", self.duplicate_mods.len())),
                         )
+                        .clicked()
                     {
-                        unfixable_modlist_modal.open();
+                        self.show_duplicate_mods_window = !self.show_duplicate_mods_window;
                     }
 
                     ui.end_row();
@@ -369,7 +2022,7 @@ impl eframe::App for RimManager {
                 egui::Grid::new("picker_grid").striped(true).show(ui, |ui| {
                     if ui.button("Game Path").clicked() {
                         let mut folder_picker =
-                            FileDialog::select_folder(Some(get_my_home().unwrap().unwrap()))
+                            FileDialog::select_folder(get_my_home().ok().flatten())
                                 .show_new_folder(false)
                                 .title("Pick a valid RimWorld installation");
                         folder_picker.open();
@@ -390,7 +2043,7 @@ impl eframe::App for RimManager {
                         .clicked()
                     {
                         let mut folder_picker =
-                            FileDialog::select_folder(Some(get_my_home().unwrap().unwrap()))
+                            FileDialog::select_folder(get_my_home().ok().flatten())
                                 .show_new_folder(false)
                                 .title("Pick a valid Steam prefix!");
                         folder_picker.open();
@@ -402,12 +2055,86 @@ impl eframe::App for RimManager {
                     }
 
                     ui.end_row();
+
+                    ui.label("Game version");
+                    ui.add_enabled_ui(can_use_mod_lists, |ui| {
+                        self.create_game_version_picker(ui);
+                    });
+
+                    ui.end_row();
+
+                    ui.menu_button("Detected installations", |ui| {
+                        let mut chosen_installation = None;
+
+                        if self.discovered_installations.is_empty() {
+                            ui.label("Nothing found");
+                        }
+
+                        for installation in &self.discovered_installations {
+                            if ui
+                                .button(format!(
+                                    "{} ({})",
+                                    installation.game_path.display(),
+                                    installation.origin
+                                ))
+                                .on_hover_text(match &installation.workshop_path {
+                                    Some(workshop_path) => {
+                                        format!("Workshop mods: {}", workshop_path.display())
+                                    }
+                                    None => "No workshop mods".to_owned(),
+                                })
+                                .clicked()
+                            {
+                                chosen_installation = Some(installation.clone());
+                            }
+                        }
+
+                        ui.separator();
+
+                        if ui.button("Search again").clicked() {
+                            self.discovered_installations = discover_installations();
+                        }
+
+                        if let Some(installation) = chosen_installation {
+                            self.game_path = Some(installation.game_path);
+                            self.steam_path = installation.steam_path;
+
+                            self.rescan();
+
+                            ui.close_menu();
+                        }
+                    });
+
+                    ui.end_row();
                 });
+
+                if let Some((scan, _)) = &self.scan_task {
+                    ui.add(
+                        egui::ProgressBar::new(scan.progress.fraction())
+                            .desired_width(200.0)
+                            .text(format!(
+                                "Scanning {}/{} mods",
+                                scan.progress.scanned.load(Ordering::Relaxed),
+                                scan.progress.total.load(Ordering::Relaxed)
+                            )),
+                    );
+                }
             });
         });
 
+        self.create_profile_panel(ctx);
+        self.create_change_banner(ctx);
+        self.create_unsupported_mods_banner(ctx);
+        self.create_notification_panel(ctx);
+        self.create_rule_db_window(ctx);
+        self.create_backup_window(ctx);
+        self.create_broken_mods_window(ctx);
+        self.create_duplicate_mods_window(ctx, can_use_mod_lists);
+
         let change_mod_active = self.create_mod_list_panel(ctx, true);
         let change_mod_inactive = self.create_mod_list_panel(ctx, false);
+        self.create_activation_modal(ctx);
+        self.create_deactivation_modal(ctx);
 
         // In reality both of these are not pressable at the same time but...
         // I can't figure out how to express this better so...
@@ -425,36 +2152,173 @@ impl eframe::App for RimManager {
             egui::ScrollArea::vertical().show(ui, |ui| {
                 ui.vertical(|ui| {
                     if let Some(selected_mod) = &self.currently_selected_mod {
-                        ui.label(selected_mod.0.as_str());
-
-                        let mod_info = if let Some(path) = self.active_mod_list.0.get(selected_mod)
-                        {
-                            path
-                        } else if let Some(path) = self.inactive_mod_list.0.get(selected_mod) {
-                            path
-                        } else {
-                            unreachable!();
+                        // The lists are empty until the first scan finishes
+                        let Some(mod_info) = self
+                            .active_mod_list
+                            .0
+                            .get(selected_mod)
+                            .or_else(|| self.inactive_mod_list.0.get(selected_mod))
+                        else {
+                            ui.label(selected_mod.0.as_str());
+                            return;
                         };
 
-                        // Work around for windows users not casing files correctly
-                        let image_path = mod_info.location.join("About").join("Preview.png");
-                        let other_possible_image_path =
-                            mod_info.location.join("About").join("preview.png");
+                        ui.horizontal(|ui| {
+                            if let Some(icon_path) = &mod_info.icon_path {
+                                ui.add(
+                                    Image::from_uri(
+                                        "file://".to_string()
+                                            + icon_path.to_string_lossy().as_ref(),
+                                    )
+                                    .max_height(32.0),
+                                );
+                            }
 
-                        let actual_path = [image_path, other_possible_image_path]
-                            .into_iter()
-                            .find(|path| path.is_file());
+                            ui.heading(&mod_info.displayable_name);
+                        });
 
                         // There is really no nice looking way to do this since RimWorld mods have all different images sizes
-                        if let Some(actual_path) = actual_path {
+                        if let Some(preview_path) = &mod_info.preview_path {
                             ui.add(
                                 Image::from_uri(
-                                    "file://".to_string() + actual_path.to_string_lossy().as_ref(),
+                                    "file://".to_string() + preview_path.to_string_lossy().as_ref(),
                                 )
                                 .max_height(500.0),
                             );
                         }
 
+                        egui::Grid::new("mod_info_grid").show(ui, |ui| {
+                            ui.label("Package id");
+                            ui.label(selected_mod.0.as_str());
+                            ui.end_row();
+
+                            if !mod_info.authors.is_empty() {
+                                ui.label("Authors");
+                                ui.label(mod_info.authors.join(", "));
+                                ui.end_row();
+                            }
+
+                            if let Some(mod_version) = &mod_info.mod_version {
+                                ui.label("Version");
+                                ui.label(mod_version);
+                                ui.end_row();
+                            }
+
+                            ui.label("Supported versions");
+                            ui.label(mod_info.supported_versions.join(", "));
+                            ui.end_row();
+
+                            if let Some(url) = &mod_info.url {
+                                ui.label("Website");
+                                ui.hyperlink(url);
+                                ui.end_row();
+                            }
+
+                            if let Some(published_file_id) = &mod_info.published_file_id {
+                                ui.label("Workshop id");
+                                ui.hyperlink_to(
+                                    published_file_id,
+                                    format!(
+                                        "https://steamcommunity.com/sharedfiles/filedetails/?id={}",
+                                        published_file_id
+                                    ),
+                                );
+                                ui.end_row();
+                            }
+
+                            ui.label("Source");
+                            ui.label(mod_info.source.to_string());
+                            ui.end_row();
+
+                            ui.label("Folder");
+                            ui.label(mod_info.location.to_string_lossy());
+                            ui.end_row();
+                        });
+
+                        if !mod_info.unreadable_versions.is_empty() {
+                            ui.separator();
+
+                            ui.colored_label(
+                                ui.visuals().warn_fg_color,
+                                format!(
+                                    "The About.xml lists versions that can't be read, anything listed under them is ignored: {}",
+                                    mod_info.unreadable_versions.join(", ")
+                                ),
+                            );
+                        }
+
+                        if !mod_info.supports_game_version {
+                            ui.separator();
+
+                            ui.colored_label(
+                                ui.visuals().warn_fg_color,
+                                format!(
+                                    "This mod doesn't declare support for RimWorld {}. {}",
+                                    self.game_version()
+                                        .map(|game_version| game_version.major_minor().to_string())
+                                        .unwrap_or_default(),
+                                    format_supported_versions(mod_info)
+                                ),
+                            );
+
+                            let mut is_overridden = self
+                                .profiles
+                                .current_version_overrides()
+                                .contains(selected_mod);
+
+                            if ui
+                                .checkbox(&mut is_overridden, "Allow it anyway in this profile")
+                                .changed()
+                            {
+                                let version_overrides =
+                                    self.profiles.current_version_overrides_mut();
+
+                                if is_overridden {
+                                    version_overrides.insert(selected_mod.clone());
+                                } else {
+                                    version_overrides.remove(selected_mod);
+                                }
+                            }
+                        }
+
+                        if let Some(game_version) = self.game_version() {
+                            ui.separator();
+
+                            ui.label("Loaded folders");
+
+                            if !mod_info.has_content_for_game_version {
+                                ui.colored_label(
+                                    ui.visuals().warn_fg_color,
+                                    format!(
+                                        "This mod has no folder for RimWorld {} so the content for an older version will be loaded, even if it claims to support it. {}",
+                                        game_version.major_minor(),
+                                        format_content_versions(mod_info)
+                                    ),
+                                );
+                            }
+
+                            // Conditions are checked against the current active list, as if this mod was in it
+                            let folders = mod_info
+                                .content_layout
+                                .folders_to_load(game_version, |package_id| {
+                                    self.active_mod_list.0.contains_key(package_id)
+                                });
+
+                            egui::Grid::new("load_folder_grid")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    for folder in folders {
+                                        ui.label(folder.path.as_str());
+                                        ui.weak(folder.conditions().join(", "));
+                                        ui.end_row();
+                                    }
+                                });
+
+                            if mod_info.content_layout.load_folders.is_none() {
+                                ui.weak("Guessed from the folder layout as there is no LoadFolders.xml");
+                            }
+                        }
+
                         ui.separator();
 
                         ui.label("Dependency issues");
@@ -478,6 +2342,66 @@ impl eframe::App for RimManager {
 
                         ui.separator();
 
+                        ui.label("Relations");
+
+                        let mut mod_to_select = None;
+
+                        egui::Grid::new("relation_grid")
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for relation in self.mod_rules.relations_of(selected_mod) {
+                                    let other_package_id = &relation.other_package_id;
+
+                                    ui.label(relation.kind.to_string());
+
+                                    let is_active =
+                                        self.active_mod_list.0.contains_key(other_package_id);
+                                    let is_installed = is_active
+                                        || self.inactive_mod_list.0.contains_key(other_package_id);
+
+                                    if is_installed {
+                                        if ui
+                                            .link(self.mod_display_name(other_package_id))
+                                            .on_hover_text(other_package_id.0.as_str())
+                                            .clicked()
+                                        {
+                                            mod_to_select = Some(other_package_id.clone());
+                                        }
+                                    } else {
+                                        ui.label(other_package_id.0.as_str());
+                                    }
+
+                                    if is_active {
+                                        ui.label("Active");
+                                    } else if is_installed {
+                                        ui.weak("Inactive");
+                                    } else if relation.kind == RelationKind::DependsOn {
+                                        ui.colored_label(
+                                            ui.visuals().warn_fg_color,
+                                            "Not installed",
+                                        );
+                                    } else {
+                                        ui.weak("Not installed");
+                                    }
+
+                                    ui.weak(match &relation.source {
+                                        ModdbType::ModBuiltRules => format!(
+                                            "From the About.xml of {}",
+                                            self.mod_display_name(&relation.declared_by)
+                                        ),
+                                        source => format!("From {}", source),
+                                    });
+
+                                    ui.end_row();
+                                }
+                            });
+
+                        if mod_to_select.is_some() {
+                            self.currently_selected_mod = mod_to_select;
+                        }
+
+                        ui.separator();
+
                         ui.label("Description");
                         // Mods use a special steam specific markdown
                         // I'm not writing a parser for that lmaooo
@@ -494,7 +2418,7 @@ impl eframe::App for RimManager {
                     if does_directory_represent_valid_game_installation(file) {
                         self.game_path = Some(file.to_path_buf());
                         self.game_path_picker_dialog = None;
-                        self.refresh_metadata().unwrap();
+                        self.rescan();
                     } else {
                         invalid_game_path_modal.open();
                     }
@@ -509,18 +2433,65 @@ impl eframe::App for RimManager {
                     if does_directory_represent_valid_steam_prefix(file) {
                         self.steam_path = Some(file.to_path_buf());
                         self.steam_path_picker_dialog = None;
-                        self.refresh_metadata().unwrap();
+                        self.rescan();
                     } else {
                         invalid_steam_path_modal.open();
                     }
                 }
             }
         }
+
+        self.save_settings_if_changed();
+        self.save_profiles_if_changed();
+    }
+}
+
+impl RimManager {
+    pub fn save_settings_if_changed(&mut self) {
+        let settings = Settings::capture(self);
+
+        if settings != self.saved_settings {
+            log::info!("Saving settings");
+
+            if let Err(error) = settings.save() {
+                log::warn!("Can't save the settings: {}", error);
+            }
+
+            // Even on failure as we don't want to retry every frame
+            self.saved_settings = settings;
+        }
+    }
+}
+
+fn format_content_versions(mod_metadata: &CondensedModMetadata) -> String {
+    format!(
+        "Only has folders for {}",
+        mod_metadata
+            .content_layout
+            .content_versions()
+            .iter()
+            .join(", ")
+    )
+}
+
+fn format_supported_versions(mod_metadata: &CondensedModMetadata) -> String {
+    if mod_metadata.supported_versions.is_empty() {
+        "Doesn't list any supported versions".to_owned()
+    } else {
+        format!(
+            "Supports {}",
+            mod_metadata.supported_versions.iter().join(", ")
+        )
     }
 }
 
 pub fn alert_box(ctx: &egui::Context, body: &str) -> Modal {
-    let alert_box = Modal::new(ctx, body);
+    alert_box_with_id(ctx, body, body)
+}
+
+/// For alert boxes whose body changes, as the modal must keep the same id between frames to stay open
+pub fn alert_box_with_id(ctx: &egui::Context, id: &str, body: &str) -> Modal {
+    let alert_box = Modal::new(ctx, id);
 
     alert_box.show(|ui| {
         alert_box.title(ui, "Non Fatal Error");
@@ -534,3 +2505,246 @@ pub fn alert_box(ctx: &egui::Context, body: &str) -> Modal {
 
     alert_box
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_folders::ContentLayout, xml::deserialize_from_xml_with_position};
+
+    fn package_id(raw: &str) -> PackageId {
+        PackageId(raw.to_owned())
+    }
+
+    fn scanned_mod(location: &str, about_xml: &str) -> ScannedMod {
+        ScannedMod {
+            location: PathBuf::from(location),
+            about: Ok((
+                deserialize_from_xml_with_position(about_xml).unwrap(),
+                ModFiles::default(),
+            )),
+        }
+    }
+
+    /// What a scan of a 1.5 installation would give back
+    fn scan_result(mods: Vec<ScannedMod>) -> ScanResult {
+        ScanResult {
+            game_version: "1.5.4104 rev435".parse().unwrap(),
+            scan_paths: Vec::new(),
+            mods,
+            errors: Vec::new(),
+        }
+    }
+
+    const ABOUT_XML_FOR_1_4: &str = r#"<ModMetaData>
+  <packageId>x.a</packageId>
+  <supportedVersions>
+    <li>1.4</li>
+  </supportedVersions>
+  <modDependenciesByVersion>
+    <v1.4>
+      <li>
+        <packageId>x.b</packageId>
+        <displayName>B</displayName>
+      </li>
+    </v1.4>
+  </modDependenciesByVersion>
+</ModMetaData>"#;
+
+    #[test]
+    fn mods_are_checked_against_the_game_version_override() {
+        let mut manager = RimManager::default();
+        manager.apply_scan_result(scan_result(vec![scanned_mod("/mods/a", ABOUT_XML_FOR_1_4)]));
+
+        let installed_game_version = Some("1.5.4104 rev435".parse().unwrap());
+        assert_eq!(manager.game_version(), installed_game_version);
+        assert!(!manager.inactive_mod_list.0[&package_id("x.a")].supports_game_version);
+        assert!(manager
+            .mod_rules
+            .dependencies_of(&package_id("x.a"))
+            .is_empty());
+
+        manager.game_version_override = Some(GameVersion::parse_major_minor("1.4").unwrap());
+        manager.apply_scan_result(scan_result(vec![scanned_mod("/mods/a", ABOUT_XML_FOR_1_4)]));
+
+        assert_eq!(manager.game_version(), manager.game_version_override);
+        assert_eq!(manager.installed_game_version, installed_game_version);
+        assert!(manager.inactive_mod_list.0[&package_id("x.a")].supports_game_version);
+        assert_eq!(
+            manager.mod_rules.dependencies_of(&package_id("x.a")),
+            BTreeSet::from([&package_id("x.b")])
+        );
+    }
+
+    #[test]
+    fn the_preferred_copy_of_a_duplicate_mod_is_used() {
+        let about_xml = |name: &str| {
+            format!(
+                "<ModMetaData><name>{}</name><packageId>x.a</packageId></ModMetaData>",
+                name
+            )
+        };
+        let scan = || {
+            scan_result(vec![
+                scanned_mod("/game/Mods/a", &about_xml("Local copy")),
+                scanned_mod("/workshop/123", &about_xml("Workshop copy")),
+                scanned_mod(
+                    "/workshop/456",
+                    "<ModMetaData><packageId>x.b</packageId></ModMetaData>",
+                ),
+            ])
+        };
+        let mut manager = RimManager::default();
+
+        // The first copy found wins without a choice
+        manager.apply_scan_result(scan());
+
+        assert_eq!(
+            manager.duplicate_mods,
+            IndexMap::from([(
+                package_id("x.a"),
+                vec![
+                    PathBuf::from("/game/Mods/a"),
+                    PathBuf::from("/workshop/123")
+                ]
+            )])
+        );
+        assert_eq!(
+            manager.inactive_mod_list.0[&package_id("x.a")].displayable_name,
+            "Local copy"
+        );
+
+        manager
+            .preferred_mod_locations
+            .insert(package_id("x.a"), PathBuf::from("/workshop/123"));
+        manager.apply_scan_result(scan());
+
+        let mod_metadata = &manager.inactive_mod_list.0[&package_id("x.a")];
+        assert_eq!(mod_metadata.displayable_name, "Workshop copy");
+        assert_eq!(mod_metadata.location, PathBuf::from("/workshop/123"));
+        assert_eq!(manager.inactive_mod_list.0.len(), 2);
+
+        // A preferred copy that went away falls back to the first one again
+        manager
+            .preferred_mod_locations
+            .insert(package_id("x.a"), PathBuf::from("/workshop/789"));
+        manager.apply_scan_result(scan());
+
+        assert_eq!(
+            manager.inactive_mod_list.0[&package_id("x.a")].location,
+            PathBuf::from("/game/Mods/a")
+        );
+    }
+
+    #[test]
+    fn mods_know_which_folder_they_were_installed_from() {
+        let about_xml = |package_id: &str| {
+            format!(
+                "<ModMetaData><packageId>{}</packageId></ModMetaData>",
+                package_id
+            )
+        };
+        let mut manager = RimManager {
+            game_path: Some(PathBuf::from("/game")),
+            mod_folder_paths: [PathBuf::from("/extra")].into_iter().collect(),
+            ..Default::default()
+        };
+
+        manager.apply_scan_result(scan_result(vec![
+            scanned_mod("/game/Data/Core", &about_xml("ludeon.rimworld")),
+            scanned_mod("/game/Mods/a", &about_xml("x.local")),
+            scanned_mod("/extra/b", &about_xml("x.custom")),
+            scanned_mod(
+                "/steam/workshop/content/294100/123",
+                &about_xml("x.workshop"),
+            ),
+        ]));
+
+        let sources: Vec<_> = manager
+            .inactive_mod_list
+            .0
+            .iter()
+            .map(|(package_id, mod_metadata)| (package_id.0.as_str(), mod_metadata.source))
+            .collect();
+
+        assert_eq!(
+            sources,
+            [
+                ("ludeon.rimworld", ModSource::Official),
+                ("x.local", ModSource::Local),
+                ("x.custom", ModSource::Custom),
+                ("x.workshop", ModSource::Workshop),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_about_xml_is_kept_in_the_condensed_metadata() {
+        let about_xml = r#"<ModMetaData>
+  <name>Mod A</name>
+  <packageId>x.a</packageId>
+  <author>Someone, Someone Else,</author>
+  <authors>
+    <li>Another</li>
+    <li>Someone</li>
+  </authors>
+  <supportedVersions>
+    <li>1.5</li>
+    <li>1.4</li>
+  </supportedVersions>
+  <modVersion>2.1</modVersion>
+  <url>https://example.com/mod-a</url>
+  <modDependencies>
+    <li>
+      <packageId>x.b</packageId>
+      <displayName>Mod B</displayName>
+    </li>
+  </modDependencies>
+</ModMetaData>"#;
+        let mod_files = ModFiles {
+            published_file_id: Some("123".to_owned()),
+            preview_path: Some(PathBuf::from("/mods/a/About/Preview.png")),
+            content_layout: ContentLayout {
+                version_folders: BTreeMap::from([(
+                    GameVersion::parse_major_minor("1.4").unwrap(),
+                    "1.4".to_owned(),
+                )]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut manager = RimManager::default();
+        manager.apply_scan_result(scan_result(vec![ScannedMod {
+            location: PathBuf::from("/mods/a"),
+            about: Ok((
+                deserialize_from_xml_with_position(about_xml).unwrap(),
+                mod_files,
+            )),
+        }]));
+
+        let mod_metadata = &manager.inactive_mod_list.0[&package_id("x.a")];
+        assert_eq!(mod_metadata.displayable_name, "Mod A");
+        assert_eq!(mod_metadata.authors, ["Another", "Someone", "Someone Else"]);
+        assert_eq!(mod_metadata.supported_versions, ["1.4", "1.5"]);
+        assert!(mod_metadata.supports_game_version);
+        // Only a 1.4 folder even though the About.xml claims 1.5
+        assert!(!mod_metadata.has_content_for_game_version);
+        assert_eq!(mod_metadata.mod_version.as_deref(), Some("2.1"));
+        assert_eq!(
+            mod_metadata.url.as_deref(),
+            Some("https://example.com/mod-a")
+        );
+        assert_eq!(mod_metadata.published_file_id.as_deref(), Some("123"));
+        assert_eq!(
+            mod_metadata.preview_path,
+            Some(PathBuf::from("/mods/a/About/Preview.png"))
+        );
+        assert_eq!(
+            mod_metadata
+                .dependencies
+                .iter()
+                .map(|info| (info.package_id.0.as_str(), info.display_name.as_str()))
+                .collect::<Vec<_>>(),
+            [("x.b", "Mod B")]
+        );
+    }
+}
@@ -0,0 +1,192 @@
+use std::{fs, path::Path};
+
+use anyhow::{anyhow, Error};
+use indexmap::IndexMap;
+
+// Valve's KeyValues text format, used by Steam for libraryfolders.vdf and the appmanifest .acf files
+// Only the subset Steam actually writes is supported. No #include or #base, and no conditionals
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VdfValue {
+    String(String),
+    Table(VdfTable),
+}
+
+/// Keys are lowercased as Steam isn't consistent about casing between versions
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VdfTable(pub IndexMap<String, VdfValue>);
+
+impl VdfTable {
+    pub fn get_string(&self, key: &str) -> Option<&str> {
+        match self.0.get(&key.to_lowercase()) {
+            Some(VdfValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_table(&self, key: &str) -> Option<&VdfTable> {
+        match self.0.get(&key.to_lowercase()) {
+            Some(VdfValue::Table(table)) => Some(table),
+            _ => None,
+        }
+    }
+}
+
+enum Token {
+    String(String),
+    Open,
+    Close,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+
+    while let Some(character) = chars.next() {
+        match character {
+            '{' => tokens.push(Token::Open),
+            '}' => tokens.push(Token::Close),
+            '"' => {
+                let mut string = String::new();
+
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => string.push('\n'),
+                            Some('t') => string.push('\t'),
+                            Some(escaped) => string.push(escaped),
+                            None => return Err(anyhow!("Unterminated string")),
+                        },
+                        Some(character) => string.push(character),
+                        None => return Err(anyhow!("Unterminated string")),
+                    }
+                }
+
+                tokens.push(Token::String(string));
+            }
+            '/' if chars.peek() == Some(&'/') => {
+                // Comment until the end of the line
+                for character in chars.by_ref() {
+                    if character == '\n' {
+                        break;
+                    }
+                }
+            }
+            character if character.is_whitespace() => (),
+            character => {
+                // Unquoted strings end at whitespace or a brace
+                let mut string = String::from(character);
+
+                while let Some(character) = chars.peek() {
+                    if character.is_whitespace() || matches!(character, '{' | '}' | '"') {
+                        break;
+                    }
+
+                    string.push(*character);
+                    chars.next();
+                }
+
+                tokens.push(Token::String(string));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn parse_table(tokens: &mut impl Iterator<Item = Token>, is_root: bool) -> Result<VdfTable, Error> {
+    let mut table = VdfTable::default();
+
+    loop {
+        let key = match tokens.next() {
+            Some(Token::String(key)) => key.to_lowercase(),
+            Some(Token::Close) if !is_root => return Ok(table),
+            None if is_root => return Ok(table),
+            Some(Token::Close) => return Err(anyhow!("Unexpected closing brace")),
+            Some(Token::Open) => return Err(anyhow!("Expected a key but found an opening brace")),
+            None => return Err(anyhow!("Missing closing brace")),
+        };
+
+        let value = match tokens.next() {
+            Some(Token::String(value)) => VdfValue::String(value),
+            Some(Token::Open) => VdfValue::Table(parse_table(tokens, false)?),
+            _ => return Err(anyhow!("Missing value for {}", key)),
+        };
+
+        table.0.insert(key, value);
+    }
+}
+
+pub fn parse_vdf(text: &str) -> Result<VdfTable, Error> {
+    parse_table(&mut tokenize(text)?.into_iter(), true)
+}
+
+pub fn read_vdf(path: &Path) -> Result<VdfTable, Error> {
+    parse_vdf(&String::from_utf8(fs::read(path)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_keys_are_lowercased_and_can_have_spaces() {
+        let vdf = parse_vdf(r#""AppState" { "Install Dir" "RimWorld" }"#).unwrap();
+
+        assert_eq!(
+            vdf.get_table("appstate")
+                .and_then(|app_state| app_state.get_string("install dir")),
+            Some("RimWorld")
+        );
+    }
+
+    #[test]
+    fn escaped_characters_are_unescaped() {
+        let vdf = parse_vdf(r#""path" "C:\\Steam\\\"Games\"\tand\nmore""#).unwrap();
+
+        assert_eq!(
+            vdf.get_string("path"),
+            Some("C:\\Steam\\\"Games\"\tand\nmore")
+        );
+    }
+
+    #[test]
+    fn nested_tables_keep_their_order() {
+        let vdf = parse_vdf(
+            r#"
+            // Written by Steam
+            "libraryfolders"
+            {
+                "1" { "path" "/b" "apps" { "294100" "123" } }
+                "0" { path /a }
+            }
+            "#,
+        )
+        .unwrap();
+        let library_folders = vdf.get_table("libraryfolders").unwrap();
+
+        assert_eq!(library_folders.0.keys().collect::<Vec<_>>(), ["1", "0"]);
+        assert_eq!(
+            library_folders
+                .get_table("1")
+                .and_then(|library| library.get_table("apps"))
+                .and_then(|apps| apps.get_string("294100")),
+            Some("123")
+        );
+        assert_eq!(
+            library_folders
+                .get_table("0")
+                .and_then(|library| library.get_string("path")),
+            Some("/a")
+        );
+    }
+
+    #[test]
+    fn broken_files_are_errors() {
+        assert!(parse_vdf(r#""key" "value"#).is_err());
+        assert!(parse_vdf(r#""key" { "a" "b""#).is_err());
+        assert!(parse_vdf(r#""key" "value" }"#).is_err());
+        assert!(parse_vdf(r#""key""#).is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Error};
+
+/// A version of RimWorld, like the 1.4.3901 rev23 in Version.txt
+/// Mods only ever talk about major.minor so the build and revision are optional
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GameVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub build: Option<u32>,
+    pub rev: Option<u32>,
+}
+
+impl GameVersion {
+    /// The part mods use in supportedVersions and the ByVersion lists
+    pub fn major_minor(self) -> Self {
+        Self {
+            build: None,
+            rev: None,
+            ..self
+        }
+    }
+
+    /// Parses a version like 1.5 and nothing more specific
+    /// The minor version has to be written out, so a mod folder named 1 isn't taken for 1.0
+    pub fn parse_major_minor(raw: &str) -> Result<Self, Error> {
+        let version: Self = raw.parse()?;
+        let has_minor = raw
+            .trim()
+            .split_once('.')
+            .is_some_and(|(_, minor)| !minor.is_empty());
+
+        if !has_minor || version != version.major_minor() {
+            return Err(anyhow!("{} is not a major.minor version", raw.trim()));
+        }
+
+        Ok(version)
+    }
+}
+
+impl FromStr for GameVersion {
+    type Err = Error;
+
+    /// Forgiving about what people write by hand
+    /// Takes 1.4.3901 rev23 from Version.txt, v1.4 from the ByVersion tags and 1 as 1.0
+    fn from_str(raw: &str) -> Result<Self, Error> {
+        let raw = raw.trim_start_matches('\u{feff}').trim();
+        let invalid = || anyhow!("\"{}\" is not a valid RimWorld version", raw);
+
+        let mut words = raw.split_whitespace();
+        let number = words.next().ok_or_else(invalid)?;
+        let number = number
+            .strip_prefix(['v', 'V'])
+            .unwrap_or(number)
+            .trim_end_matches('.');
+
+        let parts: Vec<u32> = number
+            .split('.')
+            .map(str::parse)
+            .collect::<Result<_, _>>()
+            .map_err(|_| invalid())?;
+
+        let (major, minor, build) = match parts[..] {
+            [major] => (major, 0, None),
+            [major, minor] => (major, minor, None),
+            [major, minor, build] => (major, minor, Some(build)),
+            _ => return Err(invalid()),
+        };
+
+        // Nothing depends on the revision so a weird one isn't worth failing over
+        let rev = words
+            .next()
+            .and_then(|word| word.strip_prefix("rev"))
+            .and_then(|rev| rev.parse().ok());
+
+        Ok(Self {
+            major,
+            minor,
+            build,
+            rev,
+        })
+    }
+}
+
+impl fmt::Display for GameVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+
+        if let Some(build) = self.build {
+            write!(f, ".{}", build)?;
+        }
+
+        if let Some(rev) = self.rev {
+            write!(f, " rev{}", rev)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(major: u32, minor: u32, build: Option<u32>, rev: Option<u32>) -> GameVersion {
+        GameVersion {
+            major,
+            minor,
+            build,
+            rev,
+        }
+    }
+
+    #[test]
+    fn versions_are_read_the_way_people_write_them() {
+        assert_eq!(
+            "1.4.3901 rev23".parse::<GameVersion>().unwrap(),
+            version(1, 4, Some(3901), Some(23))
+        );
+        assert_eq!(
+            "\u{feff}1.5.4104 rev435\n".parse::<GameVersion>().unwrap(),
+            version(1, 5, Some(4104), Some(435))
+        );
+        assert_eq!(
+            "1".parse::<GameVersion>().unwrap(),
+            version(1, 0, None, None)
+        );
+        assert_eq!(
+            "v1.4".parse::<GameVersion>().unwrap(),
+            version(1, 4, None, None)
+        );
+        assert_eq!(
+            " 1.3. ".parse::<GameVersion>().unwrap(),
+            version(1, 3, None, None)
+        );
+
+        assert!("1.5-beta".parse::<GameVersion>().is_err());
+        assert!("1.2.3.4".parse::<GameVersion>().is_err());
+        assert!("".parse::<GameVersion>().is_err());
+    }
+
+    #[test]
+    fn major_minor_versions_have_nothing_else() {
+        assert_eq!(
+            GameVersion::parse_major_minor("1.5").unwrap(),
+            version(1, 5, None, None)
+        );
+        assert!(GameVersion::parse_major_minor("1.5.4104").is_err());
+        assert!(GameVersion::parse_major_minor("1.5 rev435").is_err());
+        assert!(GameVersion::parse_major_minor("1.5-beta").is_err());
+        assert!(GameVersion::parse_major_minor("1").is_err());
+        assert!(GameVersion::parse_major_minor("1.").is_err());
+    }
+
+    #[test]
+    fn versions_are_ordered_by_number_not_text() {
+        let mut versions: Vec<GameVersion> = ["1.10", "1.4.3901 rev23", "1.5", "1.4", "1.4.3901"]
+            .into_iter()
+            .map(|raw| raw.parse().unwrap())
+            .collect();
+        versions.sort();
+
+        assert_eq!(
+            versions.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["1.4", "1.4.3901", "1.4.3901 rev23", "1.5", "1.10"]
+        );
+        assert_eq!(
+            "1.4.3901 rev23"
+                .parse::<GameVersion>()
+                .unwrap()
+                .major_minor(),
+            version(1, 4, None, None)
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+};
+
+use anyhow::Error;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Watching the mod folders recursively would need an inotify watch for every texture folder of every mod,
+// which runs into the watch limit on big workshop collections
+// Instead the scan folders, each mod folder and each mod's About folder are watched on their own
+
+/// What changed on disk since the last time we asked
+#[derive(Default, Debug)]
+pub struct WatchedChanges {
+    /// Mod folders that were added, removed or had their About folder touched
+    pub mod_folders: HashSet<PathBuf>,
+    pub modconfig_xml_changed: bool,
+}
+
+impl WatchedChanges {
+    pub fn is_empty(&self) -> bool {
+        self.mod_folders.is_empty() && !self.modconfig_xml_changed
+    }
+}
+
+/// Keeps an eye on the folders mods are scanned from and on ModsConfig.xml
+pub struct ModWatcher {
+    watcher: RecommendedWatcher,
+    receiver: Receiver<notify::Result<Event>>,
+    scan_paths: Vec<PathBuf>,
+    /// Not watched if we can't find it
+    modconfig_xml_path: Option<PathBuf>,
+    /// Mod folders we already put watches on
+    watched_mod_folders: HashSet<PathBuf>,
+}
+
+impl ModWatcher {
+    pub fn new(
+        scan_paths: Vec<PathBuf>,
+        modconfig_xml_path: Option<PathBuf>,
+    ) -> Result<Self, Error> {
+        let (sender, receiver) = channel();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // The receiver is only gone when the watcher is being dropped anyway
+            let _ = sender.send(event);
+        })?;
+
+        for scan_path in &scan_paths {
+            if let Err(error) = watcher.watch(scan_path, RecursiveMode::NonRecursive) {
+                log::warn!("Can't watch {}: {}", scan_path.display(), error);
+            }
+        }
+
+        // The file itself gets replaced when written atomically so watch the folder it is in
+        if let Some(modconfig_xml_dir) = modconfig_xml_path.as_deref().and_then(Path::parent) {
+            if let Err(error) = watcher.watch(modconfig_xml_dir, RecursiveMode::NonRecursive) {
+                log::warn!("Can't watch {}: {}", modconfig_xml_dir.display(), error);
+            }
+        }
+
+        Ok(Self {
+            watcher,
+            receiver,
+            scan_paths,
+            modconfig_xml_path,
+            watched_mod_folders: HashSet::new(),
+        })
+    }
+
+    /// Watches the mod folder and its About folder so edits to About.xml are noticed
+    /// Safe to call again once the About folder shows up
+    pub fn watch_mod_folder(&mut self, mod_folder: &Path) {
+        let about_folder = mod_folder.join("About");
+
+        for folder in [mod_folder, &about_folder] {
+            if self.watched_mod_folders.contains(folder) || !folder.is_dir() {
+                continue;
+            }
+
+            match self.watcher.watch(folder, RecursiveMode::NonRecursive) {
+                Ok(()) => {
+                    self.watched_mod_folders.insert(folder.to_path_buf());
+                }
+                Err(error) => log::warn!("Can't watch {}: {}", folder.display(), error),
+            }
+        }
+    }
+
+    /// Forgets about a mod folder that went away
+    pub fn unwatch_mod_folder(&mut self, mod_folder: &Path) {
+        let about_folder = mod_folder.join("About");
+
+        for folder in [mod_folder, &about_folder] {
+            if self.watched_mod_folders.remove(folder) {
+                // Fails if the folder is already gone, which removes the watch anyway
+                let _ = self.watcher.unwatch(folder);
+            }
+        }
+    }
+
+    /// The mod folder a path belongs to, if it is inside one of the scan folders
+    fn mod_folder_for_path(&self, path: &Path) -> Option<PathBuf> {
+        self.scan_paths.iter().find_map(|scan_path| {
+            let mod_folder_name = path.strip_prefix(scan_path).ok()?.components().next()?;
+
+            Some(scan_path.join(mod_folder_name))
+        })
+    }
+
+    /// Collects everything that happened since the last call without blocking
+    pub fn poll(&self) -> WatchedChanges {
+        let mut changes = WatchedChanges::default();
+
+        for event in self.receiver.try_iter() {
+            let event = match event {
+                Ok(event) => event,
+                Err(error) => {
+                    log::warn!("Error while watching the mod folders: {}", error);
+                    continue;
+                }
+            };
+
+            // Reads don't change anything
+            if matches!(event.kind, EventKind::Access(_)) {
+                continue;
+            }
+
+            for path in event.paths {
+                if self.modconfig_xml_path.as_ref() == Some(&path) {
+                    changes.modconfig_xml_changed = true;
+                } else if let Some(mod_folder) = self.mod_folder_for_path(&path) {
+                    changes.mod_folders.insert(mod_folder);
+                }
+            }
+        }
+
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temporary_dir;
+    use std::{fs, thread, time::Duration};
+
+    /// Events arrive on another thread, so keep polling for a while
+    fn wait_for_changes(watcher: &ModWatcher) -> WatchedChanges {
+        for _ in 0..50 {
+            thread::sleep(Duration::from_millis(100));
+            let mut changes = watcher.poll();
+
+            if !changes.is_empty() {
+                // Give the rest of the events a moment to come in too
+                thread::sleep(Duration::from_millis(100));
+                let late_changes = watcher.poll();
+                changes.mod_folders.extend(late_changes.mod_folders);
+                changes.modconfig_xml_changed |= late_changes.modconfig_xml_changed;
+
+                return changes;
+            }
+        }
+
+        WatchedChanges::default()
+    }
+
+    #[test]
+    fn paths_map_to_the_mod_folder_they_are_in() {
+        let dir = temporary_dir("watcher-paths");
+        let workshop = dir.join("workshop");
+        let local = dir.join("Mods");
+        fs::create_dir_all(&workshop).unwrap();
+        fs::create_dir_all(&local).unwrap();
+
+        let watcher = ModWatcher::new(vec![local.clone(), workshop.clone()], None).unwrap();
+
+        assert_eq!(
+            watcher.mod_folder_for_path(&workshop.join("123").join("About").join("About.xml")),
+            Some(workshop.join("123"))
+        );
+        assert_eq!(
+            watcher.mod_folder_for_path(&local.join("MyMod")),
+            Some(local.join("MyMod"))
+        );
+        // The scan folder itself and anything outside of the scan folders aren't mods
+        assert_eq!(watcher.mod_folder_for_path(&workshop), None);
+        assert_eq!(watcher.mod_folder_for_path(&dir.join("elsewhere")), None);
+    }
+
+    #[test]
+    fn about_xml_edits_and_mods_config_writes_are_noticed() {
+        let dir = temporary_dir("watcher-events");
+        let scan_path = dir.join("Mods");
+        let mod_folder = scan_path.join("MyMod");
+        fs::create_dir_all(mod_folder.join("About")).unwrap();
+        let modconfig_xml_path = dir.join("Config").join("ModsConfig.xml");
+        fs::create_dir_all(modconfig_xml_path.parent().unwrap()).unwrap();
+
+        let mut watcher =
+            ModWatcher::new(vec![scan_path.clone()], Some(modconfig_xml_path.clone())).unwrap();
+        watcher.watch_mod_folder(&mod_folder);
+
+        fs::write(
+            mod_folder.join("About").join("About.xml"),
+            "<ModMetaData />",
+        )
+        .unwrap();
+
+        let changes = wait_for_changes(&watcher);
+        assert_eq!(changes.mod_folders, HashSet::from([mod_folder]));
+        assert!(!changes.modconfig_xml_changed);
+
+        fs::write(&modconfig_xml_path, "<ModsConfigData />").unwrap();
+
+        let changes = wait_for_changes(&watcher);
+        assert!(changes.modconfig_xml_changed);
+        assert!(changes.mod_folders.is_empty());
+    }
+}
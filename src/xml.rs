@@ -1,20 +1,29 @@
+use std::cell::Cell;
 use std::collections::{HashMap, HashSet};
+use std::fmt;
 use std::fs;
 use std::hash::Hash;
+use std::io::{BufRead, Read};
 use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
+use crate::backups::{backup_modconfig_xml, write_file_atomically};
+use crate::errors::FileError;
 use crate::managment::{ModRelation, ModRuleDb, ModdbType, PackageId};
-use anyhow::Error;
+use crate::version::GameVersion;
+use anyhow::{anyhow, Context, Error};
 use homedir::get_my_home;
 use indexmap::IndexSet;
+use itertools::Itertools;
+use quick_xml::events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event};
+use quick_xml::DeError;
+use quick_xml::{Reader, Writer};
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Deserializer, Serialize};
 use serde_with::formats::CommaSeparator;
 use serde_with::serde_as;
-use serde_with::DisplayFromStr;
 use serde_with::StringWithSeparator;
 use url::Url;
-use versions::{Chunks, Version};
 
 // This folder contains literal XML to rust structures. As such it is not pretty nor fun to use
 // Note that quick-xml produces a XML files that RimWorld nor RimSort can parse if no mods are added
@@ -24,12 +33,88 @@ pub fn deserialize_from_xml<T: DeserializeOwned>(string: &str) -> Result<T, Erro
     Ok(quick_xml::de::from_str(string)?)
 }
 
-pub fn serialize_to_xml<T: Serialize>(data: &T) -> Result<String, Error> {
-    // quick-xml doesn't add encoding when using serde
-    Ok(format!(
-        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>{}",
-        quick_xml::se::to_string(data)?
-    ))
+/// An error from deserializing XML along with roughly where in the file it happened
+#[derive(Debug)]
+pub struct XmlError {
+    pub error: DeError,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl fmt::Display for XmlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} (around line {}, column {})",
+            self.error, self.line, self.column
+        )
+    }
+}
+
+impl std::error::Error for XmlError {}
+
+/// Hands the deserializer the text bit by bit while keeping count of how far it got
+struct PositionTrackingReader<'a> {
+    data: &'a [u8],
+    position: Rc<Cell<usize>>,
+}
+
+impl Read for PositionTrackingReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let read = (&self.data[self.position.get()..]).read(buf)?;
+        self.consume(read);
+
+        Ok(read)
+    }
+}
+
+impl BufRead for PositionTrackingReader<'_> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        Ok(&self.data[self.position.get()..])
+    }
+
+    fn consume(&mut self, amount: usize) {
+        self.position
+            .set((self.position.get() + amount).min(self.data.len()));
+    }
+}
+
+/// Lines and columns start at 1 like text editors show them
+fn find_line_and_column(string: &str, position: usize) -> (usize, usize) {
+    let before = &string.as_bytes()[..position.min(string.len())];
+    let line_start = before
+        .iter()
+        .rposition(|&byte| byte == b'\n')
+        .map_or(0, |index| index + 1);
+
+    let line = before.iter().filter(|&&byte| byte == b'\n').count() + 1;
+    let column = String::from_utf8_lossy(&before[line_start..])
+        .chars()
+        .count()
+        + 1;
+
+    (line, column)
+}
+
+/// Same as [deserialize_from_xml] but the error says where in the file it happened
+pub fn deserialize_from_xml_with_position<T: DeserializeOwned>(
+    string: &str,
+) -> Result<T, XmlError> {
+    let position = Rc::new(Cell::new(0));
+    let reader = PositionTrackingReader {
+        data: string.as_bytes(),
+        position: position.clone(),
+    };
+
+    T::deserialize(&mut quick_xml::de::Deserializer::from_reader(reader)).map_err(|error| {
+        let (line, column) = find_line_and_column(string, position.get());
+
+        XmlError {
+            error,
+            line,
+            column,
+        }
+    })
 }
 
 fn set_invalid_url_to_none<'de, D>(deserializer: D) -> Result<Option<Url>, D::Error>
@@ -43,20 +128,20 @@ where
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct ActiveMods {
     #[serde(default, rename = "li")]
     pub list: IndexSet<PackageId>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 pub struct KnownExpansions {
     #[serde(default, rename = "li")]
     pub list: IndexSet<PackageId>,
 }
 
 #[serde_as]
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// ModsConfig.xml
 pub struct ModsConfigData {
@@ -65,15 +150,34 @@ pub struct ModsConfigData {
     pub known_expansions: KnownExpansions,
 }
 
-#[serde_as]
-#[derive(Debug, Deserialize)]
+/// Kept as written and only parsed when used, so one odd entry like 1.5-beta doesn't break the whole mod
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SupportedVersions {
     #[serde(default, rename = "li")]
-    #[serde_as(as = "HashSet<DisplayFromStr>")]
-    pub list: HashSet<Version>,
+    pub list: Vec<String>,
 }
 
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Hash)]
+/// The *ByVersion tags, where every child is named after a version like <v1.4>
+/// The names are kept as written for the same reason as [SupportedVersions]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct ByVersion<T>(pub HashMap<String, T>);
+
+impl<T> ByVersion<T> {
+    /// The entry for this major.minor version, names that aren't versions never match
+    pub fn get(&self, version: GameVersion) -> Option<&T> {
+        self.0
+            .iter()
+            .find(|(raw_version, _)| {
+                raw_version
+                    .parse::<GameVersion>()
+                    .is_ok_and(|entry_version| entry_version.major_minor() == version.major_minor())
+            })
+            .map(|(_, entry)| entry)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct ModDependencyInfo {
     /// Id for the mod
@@ -85,70 +189,38 @@ pub struct ModDependencyInfo {
     pub steam_workshop_url: Option<Url>,
 }
 
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct ModDependencies {
     #[serde(default, rename = "li")]
     pub list: Vec<ModDependencyInfo>,
 }
 
-#[serde_as]
-#[derive(Default, Debug, Deserialize)]
-pub struct ModDependenciesByVersion {
-    #[serde(default, rename = "li")]
-    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    pub map: HashMap<Version, Vec<ModDependencyInfo>>,
-}
-
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct LoadAfter {
     #[serde(default, rename = "li")]
     pub list: HashSet<PackageId>,
 }
 
-#[serde_as]
-#[derive(Default, Debug, Deserialize)]
-pub struct LoadAfterByVersion {
-    #[serde(default, rename = "li")]
-    #[serde_as(as = "HashMap<DisplayFromStr, _>")]
-    pub map: HashMap<Version, HashSet<PackageId>>,
-}
-
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct LoadBefore {
     #[serde(default, rename = "li")]
     pub list: HashSet<PackageId>,
 }
 
-#[serde_as]
-#[derive(Default, Debug, Deserialize)]
-pub struct LoadBeforeByVersion {
-    #[serde(default, rename = "li")]
-    #[serde_as(as = "HashMap<DisplayFromStr, HashSet<_>>")]
-    pub map: HashMap<Version, HashSet<PackageId>>,
-}
-
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct IncompatibleWith {
     #[serde(default, rename = "li")]
     pub list: HashSet<PackageId>,
 }
 
-#[serde_as]
-#[derive(Default, Debug, Deserialize)]
-pub struct IncompatibleWithByVersion {
-    #[serde(default, rename = "li")]
-    #[serde_as(as = "HashMap<DisplayFromStr, HashSet<_>>")]
-    pub map: HashMap<Version, HashSet<PackageId>>,
-}
-
-#[derive(Default, Debug, Deserialize)]
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
 pub struct Authors {
     #[serde(default, rename = "li")]
     pub list: HashSet<String>,
 }
 
 #[serde_as]
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 /// About.xml
 /// This is a mess to try to handle all the horrible edge cases for peoples
@@ -166,29 +238,35 @@ pub struct ModMetaData {
     pub description: String,
     /// Versions of RimWorld this mod can be run with
     pub supported_versions: Option<SupportedVersions>,
+    /// Whatever version the author gave their own mod
+    pub mod_version: Option<String>,
+    /// Usually a link to the mod's GitHub or forum page
+    pub url: Option<String>,
+    /// Texture path of the mod's icon, About/ModIcon.png is used without it
+    pub mod_icon_path: Option<String>,
     /// The package id the author made up
     pub package_id: PackageId,
     /// Dependency graph stuff
     #[serde(default)]
     load_before: LoadBefore,
     #[serde(default)]
-    load_before_by_version: LoadBeforeByVersion,
+    load_before_by_version: ByVersion<LoadBefore>,
     #[serde(default)]
     force_load_before: LoadBefore,
     #[serde(default)]
     load_after: LoadAfter,
     #[serde(default)]
-    load_after_by_version: LoadAfterByVersion,
+    load_after_by_version: ByVersion<LoadAfter>,
     #[serde(default)]
     force_load_after: LoadAfter,
     #[serde(default)]
     mod_dependencies: ModDependencies,
     #[serde(default)]
-    mod_dependencies_by_version: ModDependenciesByVersion,
+    mod_dependencies_by_version: ByVersion<ModDependencies>,
     #[serde(default)]
     incompatible_with: IncompatibleWith,
     #[serde(default)]
-    incompatible_with_by_version: IncompatibleWithByVersion,
+    incompatible_with_by_version: ByVersion<IncompatibleWith>,
 }
 
 impl ModMetaData {
@@ -206,52 +284,81 @@ impl ModMetaData {
         real_authors
     }
 
-    pub fn does_mod_support_this_version(&self, version: Version) -> bool {
-        let relevant_version = Version {
-            epoch: None,
-            chunks: Chunks(vec![
-                version.chunks.0[0].clone(),
-                version.chunks.0[1].clone(),
-            ]),
-            release: None,
-            meta: None,
-        };
+    /// The readable versions in supportedVersions
+    fn supported_game_versions(&self) -> impl Iterator<Item = GameVersion> + '_ {
+        self.supported_versions
+            .iter()
+            .flat_map(|supported_versions| &supported_versions.list)
+            .filter_map(|raw_version| raw_version.parse().ok())
+    }
 
+    pub fn does_mod_support_this_version(&self, version: GameVersion) -> bool {
         // Base game data files don't include this
-        if let Some(supported_versions) = &self.supported_versions {
-            return supported_versions.list.contains(&relevant_version);
+        if self.supported_versions.is_none() {
+            return true;
+        }
+
+        self.supported_game_versions()
+            .any(|supported_version| supported_version.major_minor() == version.major_minor())
+    }
+
+    /// The versions listed in supportedVersions, oldest first
+    pub fn supported_version_names(&self) -> Vec<String> {
+        self.supported_game_versions()
+            .sorted()
+            .dedup()
+            .map(|version| version.to_string())
+            .collect()
+    }
+
+    /// Versions in supportedVersions and the ByVersion tags that we can't make sense of
+    /// Whatever is listed under them is ignored, so the user gets to see them
+    pub fn unreadable_versions(&self) -> Vec<String> {
+        let supported_versions = self
+            .supported_versions
+            .iter()
+            .flat_map(|supported_versions| &supported_versions.list);
+        let by_version_names = self
+            .load_before_by_version
+            .0
+            .keys()
+            .chain(self.load_after_by_version.0.keys())
+            .chain(self.mod_dependencies_by_version.0.keys())
+            .chain(self.incompatible_with_by_version.0.keys());
+
+        supported_versions
+            .chain(by_version_names)
+            .filter(|raw_version| raw_version.parse::<GameVersion>().is_err())
+            .map(|raw_version| raw_version.trim().to_owned())
+            .sorted()
+            .dedup()
+            .collect()
+    }
+
+    /// The modDependencies that apply to a version, including the ones only listed for it
+    pub fn dependencies_for_version(&self, version: GameVersion) -> Vec<ModDependencyInfo> {
+        let mut dependencies = self.mod_dependencies.list.clone();
+
+        if let Some(mod_dependencies_by_version) = self.mod_dependencies_by_version.get(version) {
+            dependencies.extend(mod_dependencies_by_version.list.iter().cloned());
         }
 
-        true
+        dependencies
     }
 
     pub fn load_dependency_information_for_version(
         &self,
-        version: Version,
+        version: GameVersion,
         mod_rule_db: &mut ModRuleDb,
     ) {
         let data = &mut mod_rule_db
-            .0
+            .dbs
             .entry(ModdbType::ModBuiltRules)
             .or_default()
             .entry(self.package_id.clone())
             .or_default()
             .rules;
 
-        let relevant_version = Version {
-            epoch: None,
-            chunks: Chunks(vec![
-                version.chunks.0[0].clone(),
-                version.chunks.0[1].clone(),
-            ]),
-            release: None,
-            meta: None,
-        };
-
-        if !self.does_mod_support_this_version(version) {
-            return;
-        }
-
         data.extend(
             self.load_before
                 .list
@@ -260,10 +367,10 @@ impl ModMetaData {
                 .map(|id| (id, ModRelation::Before)),
         );
 
-        if let Some(load_before_by_version) = self.load_before_by_version.map.get(&relevant_version)
-        {
+        if let Some(load_before_by_version) = self.load_before_by_version.get(version) {
             data.extend(
                 load_before_by_version
+                    .list
                     .iter()
                     .cloned()
                     .map(|id| (id, ModRelation::Before)),
@@ -286,9 +393,10 @@ impl ModMetaData {
                 .map(|id| (id, ModRelation::After)),
         );
 
-        if let Some(load_after_by_version) = self.load_after_by_version.map.get(&relevant_version) {
+        if let Some(load_after_by_version) = self.load_after_by_version.get(version) {
             data.extend(
                 load_after_by_version
+                    .list
                     .iter()
                     .cloned()
                     .map(|id| (id, ModRelation::After)),
@@ -310,11 +418,10 @@ impl ModMetaData {
                 .map(|info| (info.package_id.clone(), ModRelation::Dependency)),
         );
 
-        if let Some(mod_dependencies_by_version) =
-            self.mod_dependencies_by_version.map.get(&relevant_version)
-        {
+        if let Some(mod_dependencies_by_version) = self.mod_dependencies_by_version.get(version) {
             data.extend(
                 mod_dependencies_by_version
+                    .list
                     .iter()
                     .map(|info| (info.package_id.clone(), ModRelation::Dependency)),
             );
@@ -328,11 +435,10 @@ impl ModMetaData {
                 .map(|id| (id, ModRelation::Incompatibility)),
         );
 
-        if let Some(incompatible_with_by_version) =
-            self.incompatible_with_by_version.map.get(&relevant_version)
-        {
+        if let Some(incompatible_with_by_version) = self.incompatible_with_by_version.get(version) {
             data.extend(
                 incompatible_with_by_version
+                    .list
                     .iter()
                     .cloned()
                     .map(|id| (id, ModRelation::Incompatibility)),
@@ -345,20 +451,63 @@ pub fn read_about_xml(mod_location: &Path) -> Result<ModMetaData, Error> {
     let mut about_file_location = mod_location.to_path_buf();
     about_file_location.extend(["About", "About.xml"]);
 
-    let about_file_data = fs::read(about_file_location)?;
+    let about_file_data = fs::read(about_file_location).context("Can't read About/About.xml")?;
     // I'm gonna take the assumption all RimWorld xml files are in utf8 without checking because so far that seems to be the case
     // TODO: Check if this is always true
     let about_file_string = String::from_utf8(about_file_data)?;
-    let about_xml = deserialize_from_xml(&about_file_string)?;
+    let about_xml = deserialize_from_xml_with_position(&about_file_string)?;
 
     Ok(about_xml)
 }
 
-fn resolve_modconfig_xml_path() -> PathBuf {
+/// Digs the packageId and name out of an About.xml that can't be deserialized
+/// Gives up quietly at the first thing it can't read
+pub fn recover_about_xml_identity(mod_location: &Path) -> (Option<PackageId>, Option<String>) {
+    let Ok(about_file_data) = fs::read(mod_location.join("About").join("About.xml")) else {
+        return (None, None);
+    };
+    let about_file_string = String::from_utf8_lossy(&about_file_data);
+
+    let mut reader = Reader::from_str(&about_file_string);
+    reader.trim_text(true);
+    reader.check_end_names(false);
+
+    let mut element_names = Vec::new();
+    let mut package_id = None;
+    let mut name = None;
+
+    loop {
+        match reader.read_event() {
+            Ok(Event::Start(start)) => element_names.push(start.local_name().as_ref().to_vec()),
+            Ok(Event::End(_)) => {
+                element_names.pop();
+            }
+            // Only direct children of ModMetaData, as dependencies have a packageId too
+            Ok(Event::Text(text)) if element_names.len() == 2 => {
+                let Ok(text) = text.unescape() else {
+                    continue;
+                };
+
+                match element_names[1].as_slice() {
+                    b"packageId" if package_id.is_none() => {
+                        package_id = Some(PackageId(text.trim().to_lowercase()))
+                    }
+                    b"name" if name.is_none() => name = Some(text.trim().to_owned()),
+                    _ => (),
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => (),
+        }
+    }
+
+    (package_id, name)
+}
+
+pub fn resolve_modconfig_xml_path() -> Option<PathBuf> {
     #[cfg(target_os = "linux")]
     let base_path = get_my_home()
-        .unwrap()
-        .unwrap()
+        .ok()??
         .join(".config")
         .join("unity3d")
         .join("Ludeon Studios")
@@ -367,8 +516,7 @@ fn resolve_modconfig_xml_path() -> PathBuf {
 
     #[cfg(target_os = "macos")]
     let base_path = get_my_home()
-        .unwrap()
-        .unwrap()
+        .ok()??
         .join("Library")
         .join("Application Support")
         .join("RimWorld")
@@ -376,31 +524,472 @@ fn resolve_modconfig_xml_path() -> PathBuf {
 
     #[cfg(target_os = "windows")]
     let base_path = get_my_home()
-        .unwrap()
-        .unwrap()
+        .ok()??
         .join("AppData")
         .join("LocalLow")
         .join("Ludeon Studios")
         .join("RimWorld by Ludeon Studios")
         .join("Config");
 
-    base_path.join("ModsConfig.xml")
+    Some(base_path.join("ModsConfig.xml"))
+}
+
+pub fn read_modconfig_xml() -> Result<ModsConfigData, FileError> {
+    read_modconfig_xml_from(&resolve_modconfig_xml_path().ok_or(FileError::MissingHomeDir)?)
+}
+
+/// Same as [read_modconfig_xml] but for a ModsConfig.xml that isn't the game's own
+pub fn read_modconfig_xml_from(modconfig_xml_path: &Path) -> Result<ModsConfigData, FileError> {
+    let read = || -> Result<ModsConfigData, Error> {
+        let modconfig_xml_data = fs::read(modconfig_xml_path)?;
+        let modconfig_xml_string = String::from_utf8(modconfig_xml_data)?;
+
+        deserialize_from_xml(&modconfig_xml_string)
+    };
+
+    read().map_err(|cause| FileError::ReadModsConfig {
+        path: modconfig_xml_path.to_path_buf(),
+        cause,
+    })
+}
+
+/// Same as [read_modconfig_xml] but starts from an empty list when the game hasn't written the file yet
+pub fn read_modconfig_xml_or_default() -> Result<ModsConfigData, FileError> {
+    read_modconfig_xml_or_default_from(
+        &resolve_modconfig_xml_path().ok_or(FileError::MissingHomeDir)?,
+    )
 }
 
-pub fn read_modconfig_xml() -> Result<ModsConfigData, Error> {
-    let modconfig_xml_path = resolve_modconfig_xml_path();
+/// Same as [read_modconfig_xml_or_default] but for a ModsConfig.xml that isn't the game's own
+pub fn read_modconfig_xml_or_default_from(
+    modconfig_xml_path: &Path,
+) -> Result<ModsConfigData, FileError> {
+    match modconfig_xml_path.try_exists() {
+        Ok(false) => Ok(ModsConfigData::default()),
+        _ => read_modconfig_xml_from(modconfig_xml_path),
+    }
+}
 
-    let modconfig_xml_data = fs::read(modconfig_xml_path)?;
-    let modconfig_xml_string = String::from_utf8(modconfig_xml_data)?;
-    let modconfig_xml = deserialize_from_xml(&modconfig_xml_string)?;
+pub fn write_modconfig_xml(config: &ModsConfigData) -> Result<(), FileError> {
+    write_modconfig_xml_to(
+        config,
+        &resolve_modconfig_xml_path().ok_or(FileError::MissingHomeDir)?,
+    )
+}
 
-    Ok(modconfig_xml)
+/// Same as [write_modconfig_xml] but for a ModsConfig.xml that isn't the game's own
+pub fn write_modconfig_xml_to(
+    config: &ModsConfigData,
+    modconfig_xml_path: &Path,
+) -> Result<(), FileError> {
+    let write = || -> Result<(), Error> {
+        // Edit the existing file where we can so we don't lose what we don't understand
+        let data = match fs::read(modconfig_xml_path) {
+            Ok(original) => update_modconfig_xml(&String::from_utf8(original)?, config)?,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+                new_modconfig_xml(config)?
+            }
+            Err(error) => return Err(error.into()),
+        };
+
+        backup_modconfig_xml(modconfig_xml_path)?;
+        write_file_atomically(modconfig_xml_path, data.as_bytes())?;
+
+        Ok(())
+    };
+
+    write().map_err(|cause| FileError::WriteModsConfig {
+        path: modconfig_xml_path.to_path_buf(),
+        cause,
+    })
 }
 
-pub fn write_modconfig_xml(config: &ModsConfigData) -> Result<(), Error> {
-    let modconfig_xml_path = resolve_modconfig_xml_path();
+/// A ModsConfig.xml for when the game hasn't written one yet
+/// Written by hand rather than through serde so every element gets a start and end tag
+pub fn new_modconfig_xml(config: &ModsConfigData) -> Result<String, Error> {
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("utf-8"), None)))?;
+    writer.write_event(Event::Start(BytesStart::new("ModsConfigData")))?;
 
-    fs::write(modconfig_xml_path, serialize_to_xml(config)?.as_bytes())?;
+    writer.write_event(Event::Start(BytesStart::new("version")))?;
+    writer.write_event(Event::Text(BytesText::new(&config.version)))?;
+    writer.write_event(Event::End(BytesEnd::new("version")))?;
+
+    write_active_mods(&mut writer, config)?;
+
+    writer.write_event(Event::Start(BytesStart::new("knownExpansions")))?;
+
+    for package_id in &config.known_expansions.list {
+        writer.write_event(Event::Start(BytesStart::new("li")))?;
+        writer.write_event(Event::Text(BytesText::new(&package_id.0)))?;
+        writer.write_event(Event::End(BytesEnd::new("li")))?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::new("knownExpansions")))?;
+    writer.write_event(Event::End(BytesEnd::new("ModsConfigData")))?;
+
+    let mut xml = String::from_utf8(writer.into_inner())?;
+    // RimWorld ends its files with a newline
+    xml.push('\n');
+
+    Ok(xml)
+}
+
+fn write_active_mods<W: std::io::Write>(
+    writer: &mut Writer<W>,
+    config: &ModsConfigData,
+) -> Result<(), Error> {
+    // Always a start and end tag, as an empty tag is one of the things that trips up RimWorld
+    writer.write_event(Event::Start(BytesStart::new("activeMods")))?;
+
+    for package_id in &config.active_mods.list {
+        writer.write_event(Event::Start(BytesStart::new("li")))?;
+        writer.write_event(Event::Text(BytesText::new(&package_id.0)))?;
+        writer.write_event(Event::End(BytesEnd::new("li")))?;
+    }
+
+    writer.write_event(Event::End(BytesEnd::new("activeMods")))?;
 
     Ok(())
 }
+
+/// Replaces the activeMods in an existing ModsConfig.xml with the ones in the config
+/// Everything else, including comments and elements we don't model, is carried over as is and the result is indented like RimWorld's own file
+pub fn update_modconfig_xml(original: &str, config: &ModsConfigData) -> Result<String, Error> {
+    let mut reader = Reader::from_str(original);
+    reader.trim_text(true);
+
+    let mut writer = Writer::new_with_indent(Vec::new(), b' ', 2);
+    let mut depth = 0;
+    let mut has_declaration = false;
+    let mut wrote_active_mods = false;
+    // Depth of the activeMods element we are skipping the contents of
+    let mut skipping_until = None;
+
+    loop {
+        let event = reader.read_event()?;
+
+        if let Some(skip_depth) = skipping_until {
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => {
+                    depth -= 1;
+
+                    if depth == skip_depth {
+                        skipping_until = None;
+                    }
+                }
+                Event::Eof => return Err(anyhow!("activeMods is never closed")),
+                _ => (),
+            }
+
+            continue;
+        }
+
+        match event {
+            Event::Decl(_) => {
+                has_declaration = true;
+                writer.write_event(event)?;
+            }
+            Event::Start(ref start) if depth == 1 && start.name().as_ref() == b"activeMods" => {
+                write_active_mods(&mut writer, config)?;
+                wrote_active_mods = true;
+                skipping_until = Some(depth);
+                depth += 1;
+            }
+            Event::Empty(ref start) if depth == 1 && start.name().as_ref() == b"activeMods" => {
+                write_active_mods(&mut writer, config)?;
+                wrote_active_mods = true;
+            }
+            // A root element with nothing in it yet, opened up so activeMods can go inside
+            Event::Empty(ref start) if depth == 0 => {
+                let name = String::from_utf8(start.name().as_ref().to_vec())?;
+                // Rebuilt from the attributes as the space before the /> would otherwise be kept
+                let opened = BytesStart::new(name.as_str())
+                    .with_attributes(start.attributes().collect::<Result<Vec<_>, _>>()?);
+
+                writer.write_event(Event::Start(opened))?;
+                write_active_mods(&mut writer, config)?;
+                wrote_active_mods = true;
+                writer.write_event(Event::End(BytesEnd::new(name)))?;
+            }
+            Event::Start(_) => {
+                depth += 1;
+                writer.write_event(event)?;
+            }
+            Event::End(_) => {
+                depth -= 1;
+
+                // Closing the root element without ever seeing activeMods
+                if depth == 0 && !wrote_active_mods {
+                    write_active_mods(&mut writer, config)?;
+                    wrote_active_mods = true;
+                }
+
+                writer.write_event(event)?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    if !wrote_active_mods {
+        return Err(anyhow!("ModsConfig.xml has no root element"));
+    }
+
+    let mut xml = String::from_utf8(writer.into_inner())?;
+
+    if !has_declaration {
+        xml.insert_str(0, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    }
+
+    // RimWorld ends its files with a newline
+    xml.push('\n');
+
+    Ok(xml)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(active_mods: &[&str]) -> ModsConfigData {
+        ModsConfigData {
+            version: "1.5.4104 rev435".to_string(),
+            active_mods: ActiveMods {
+                list: active_mods
+                    .iter()
+                    .map(|package_id| PackageId(package_id.to_string()))
+                    .collect(),
+            },
+            known_expansions: KnownExpansions {
+                list: IndexSet::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn only_the_active_mods_are_replaced() {
+        let original = r#"<?xml version="1.0" encoding="utf-8"?>
+<ModsConfigData>
+<!-- written by hand -->
+<version>1.5.4104 rev435</version>
+<activeMods><li>ludeon.rimworld</li><li>old.mod</li></activeMods>
+<knownExpansions><li>ludeon.rimworld.royalty</li></knownExpansions>
+<someFutureSetting enabled="true" />
+</ModsConfigData>"#;
+
+        let updated =
+            update_modconfig_xml(original, &config(&["ludeon.rimworld", "new.mod"])).unwrap();
+
+        assert_eq!(
+            updated,
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ModsConfigData>
+  <!-- written by hand -->
+  <version>1.5.4104 rev435</version>
+  <activeMods>
+    <li>ludeon.rimworld</li>
+    <li>new.mod</li>
+  </activeMods>
+  <knownExpansions>
+    <li>ludeon.rimworld.royalty</li>
+  </knownExpansions>
+  <someFutureSetting enabled="true" />
+</ModsConfigData>
+"#
+        );
+    }
+
+    #[test]
+    fn missing_active_mods_are_added() {
+        let updated = update_modconfig_xml(
+            "<ModsConfigData><activeMods /></ModsConfigData>",
+            &config(&["ludeon.rimworld"]),
+        )
+        .unwrap();
+        assert!(updated.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n"));
+        assert!(updated.contains("<activeMods>\n    <li>ludeon.rimworld</li>\n  </activeMods>"));
+
+        let updated = update_modconfig_xml(
+            "<ModsConfigData><version>1.5</version></ModsConfigData>",
+            &config(&["ludeon.rimworld"]),
+        )
+        .unwrap();
+        assert!(updated.contains(
+            "<version>1.5</version>\n  <activeMods>\n    <li>ludeon.rimworld</li>\n  </activeMods>\n</ModsConfigData>"
+        ));
+
+        let updated = update_modconfig_xml(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ModsConfigData version=\"1\" />",
+            &config(&["ludeon.rimworld"]),
+        )
+        .unwrap();
+        assert_eq!(
+            updated,
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<ModsConfigData version=\"1\">\n  <activeMods>\n    <li>ludeon.rimworld</li>\n  </activeMods>\n</ModsConfigData>\n"
+        );
+
+        assert!(update_modconfig_xml("", &config(&[])).is_err());
+    }
+
+    #[test]
+    fn missing_mods_config_starts_empty() {
+        let modconfig_xml_path = crate::temporary_dir("missing-mods-config").join("ModsConfig.xml");
+
+        let mut mod_config_data = read_modconfig_xml_or_default_from(&modconfig_xml_path).unwrap();
+        assert!(mod_config_data.active_mods.list.is_empty());
+        assert!(read_modconfig_xml_from(&modconfig_xml_path).is_err());
+
+        mod_config_data.version = "1.5.4104 rev435".to_string();
+        mod_config_data.active_mods = config(&["ludeon.rimworld"]).active_mods;
+        write_modconfig_xml_to(&mod_config_data, &modconfig_xml_path).unwrap();
+
+        // No self-closing tags, even for the empty knownExpansions
+        assert_eq!(
+            fs::read_to_string(&modconfig_xml_path).unwrap(),
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ModsConfigData>
+  <version>1.5.4104 rev435</version>
+  <activeMods>
+    <li>ludeon.rimworld</li>
+  </activeMods>
+  <knownExpansions>
+  </knownExpansions>
+</ModsConfigData>
+"#
+        );
+
+        let written = read_modconfig_xml_from(&modconfig_xml_path).unwrap();
+        assert_eq!(written.version, "1.5.4104 rev435");
+        assert_eq!(
+            written.active_mods.list.into_iter().collect::<Vec<_>>(),
+            vec![PackageId("ludeon.rimworld".to_string())]
+        );
+        assert!(written.known_expansions.list.is_empty());
+    }
+
+    #[test]
+    fn errors_say_where_they_happened() {
+        let error = deserialize_from_xml_with_position::<ModMetaData>(
+            "<ModMetaData>\n  <name>No Id</name>\n  <author>Someone</author>\n</ModMetaData>",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "missing field `packageId` (around line 4, column 15)"
+        );
+
+        let error = deserialize_from_xml_with_position::<ModMetaData>(
+            "<ModMetaData>\n  <packageId>test.escape</packageId>\n  <name>Salt &amp Pepper</name>\n</ModMetaData>\n",
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "Error while escaping character at range 5..16: Cannot find ';' after '&' (around line 3, column 32)");
+    }
+
+    #[test]
+    fn identity_is_recovered_from_broken_about_xml() {
+        let mod_folder = crate::temporary_dir("broken-about-xml");
+        fs::create_dir_all(mod_folder.join("About")).unwrap();
+        fs::write(
+            mod_folder.join("About").join("About.xml"),
+            r#"<?xml version="1.0" encoding="utf-8"?>
+<ModMetaData>
+  <modDependencies>
+    <li>
+      <packageId>brrainz.harmony</packageId>
+      <displayName>Harmony</displayName>
+    </li>
+  </modDependencies>
+  <packageId>  Test.Broken </packageId>
+  <name>Broken &amp; Proud</name>
+  <description>Uses an & on its own</description>
+  <supportedVersions>
+"#,
+        )
+        .unwrap();
+
+        assert!(deserialize_from_xml_with_position::<ModMetaData>(
+            &fs::read_to_string(mod_folder.join("About").join("About.xml")).unwrap()
+        )
+        .is_err());
+        assert_eq!(
+            recover_about_xml_identity(&mod_folder),
+            (
+                Some(PackageId("test.broken".to_owned())),
+                Some("Broken & Proud".to_owned())
+            )
+        );
+
+        // Nothing to recover without an About.xml
+        assert_eq!(
+            recover_about_xml_identity(&mod_folder.join("missing")),
+            (None, None)
+        );
+    }
+
+    const ABOUT_XML_WITH_ODD_VERSIONS: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<ModMetaData>
+  <name>Odd Versions</name>
+  <packageId>test.odd</packageId>
+  <supportedVersions>
+    <li>1.4</li>
+    <li>1.5-beta</li>
+  </supportedVersions>
+  <loadAfterByVersion>
+    <v1.4>
+      <li>test.after</li>
+    </v1.4>
+    <v1.5-beta>
+      <li>test.never</li>
+    </v1.5-beta>
+  </loadAfterByVersion>
+  <modDependenciesByVersion>
+    <v1.4>
+      <li>
+        <packageId>test.dependency</packageId>
+        <displayName>Dependency</displayName>
+      </li>
+    </v1.4>
+  </modDependenciesByVersion>
+</ModMetaData>"#;
+
+    #[test]
+    fn unreadable_versions_are_skipped_instead_of_breaking_the_mod() {
+        let about: ModMetaData =
+            deserialize_from_xml_with_position(ABOUT_XML_WITH_ODD_VERSIONS).unwrap();
+
+        assert_eq!(about.supported_version_names(), ["1.4"]);
+        assert_eq!(about.unreadable_versions(), ["1.5-beta", "v1.5-beta"]);
+        assert!(about.does_mod_support_this_version("1.4.3901 rev23".parse().unwrap()));
+        assert!(!about.does_mod_support_this_version("1.5".parse().unwrap()));
+    }
+
+    #[test]
+    fn by_version_rules_only_apply_to_their_version() {
+        let about: ModMetaData =
+            deserialize_from_xml_with_position(ABOUT_XML_WITH_ODD_VERSIONS).unwrap();
+        let package_id = PackageId("test.odd".to_owned());
+
+        let mut rule_db = ModRuleDb::default();
+        about.load_dependency_information_for_version("1.4.3901".parse().unwrap(), &mut rule_db);
+        let rules = &rule_db.dbs[&ModdbType::ModBuiltRules][&package_id].rules;
+
+        assert_eq!(rules.len(), 2);
+        assert_eq!(
+            rules[&PackageId("test.after".to_owned())],
+            ModRelation::After
+        );
+        assert_eq!(
+            rules[&PackageId("test.dependency".to_owned())],
+            ModRelation::Dependency
+        );
+
+        let mut rule_db = ModRuleDb::default();
+        about.load_dependency_information_for_version("1.5".parse().unwrap(), &mut rule_db);
+        assert!(rule_db.dbs[&ModdbType::ModBuiltRules][&package_id]
+            .rules
+            .is_empty());
+    }
+}